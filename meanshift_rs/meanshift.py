@@ -14,7 +14,7 @@ class MeanShift:
     Arguments
     ---------
     n_threads : int
-        Threads used for running the algorithm (default=-1).
+        Threads used for running the algorithm, -1 uses all available cores (default=-1).
     bandwidth : Optional[float]
         Evtl. bandwidth value. If None, it will be estimated (default=None).
     distance_measure : str
@@ -0,0 +1,851 @@
+use crate::distance_measure::DistanceMeasure;
+use crate::error::{MeanShiftError, Result};
+use crate::params::{validate_bandwidth, ParameterError};
+use crate::utils::{validate_weights, LibData};
+use ndarray::{Array1, Array2, ArrayView1, ArrayView2, Axis, CowArray, Ix1, Ix2};
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+use rand::SeedableRng;
+use rayon::prelude::*;
+use std::cmp::Ordering;
+
+/// Rules that estimate a single bandwidth for all dimensions.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BandwidthEstimator {
+    /// Average distance of the rows to their `quantile * n`-th nearest neighbor, see
+    /// [`estimate_bandwidth`]. By default among [`DEFAULT_N_SAMPLES`] drawn rows.
+    KnnQuantile {
+        quantile: f64,
+        n_samples: Option<usize>,
+        seed: u64,
+    },
+    /// Scott's rule `σ n^(-1 / (d + 4))`, with `σ²` the average variance of the columns.
+    Scott,
+    /// Silverman's rule `σ (4 / ((d + 2) n))^(1 / (d + 4))`, with `σ²` the average variance of
+    /// the columns.
+    Silverman,
+    /// Median distance between all pairs of `n_samples` rows drawn with an RNG seeded by `seed`.
+    MedianPairwise { n_samples: Option<usize>, seed: u64 },
+}
+
+/// Quantile of the default `BandwidthEstimator::KnnQuantile`.
+pub(crate) const DEFAULT_QUANTILE: f64 = 0.3;
+
+/// Rows drawn by the default `BandwidthEstimator::KnnQuantile`, which compares all pairs of
+/// them.
+pub const DEFAULT_N_SAMPLES: usize = 1000;
+
+impl Default for BandwidthEstimator {
+    fn default() -> Self {
+        Self::KnnQuantile {
+            quantile: DEFAULT_QUANTILE,
+            n_samples: Some(DEFAULT_N_SAMPLES),
+            seed: 0,
+        }
+    }
+}
+
+impl BandwidthEstimator {
+    pub(crate) fn validate(&self) -> std::result::Result<(), ParameterError> {
+        match self {
+            Self::KnnQuantile {
+                quantile,
+                n_samples,
+                ..
+            } => {
+                validate_quantile(*quantile)?;
+                validate_n_samples(*n_samples)
+            }
+            Self::MedianPairwise { n_samples, .. } => validate_n_samples(*n_samples),
+            _ => Ok(()),
+        }
+    }
+
+    /// Estimates the bandwidth of `data` under the distance measure `D`.
+    pub fn estimate<A: LibData, D: DistanceMeasure<A>>(&self, data: ArrayView2<A>) -> Result<A> {
+        self.estimate_with::<A, D>(data, None)
+    }
+
+    /// Estimates the bandwidth of `data`, counting every row `weights[i]` times.
+    pub fn estimate_weighted<A: LibData, D: DistanceMeasure<A>>(
+        &self,
+        data: ArrayView2<A>,
+        weights: ArrayView1<A>,
+    ) -> Result<A> {
+        validate_weights(weights, data.shape()[0])?;
+        self.estimate_with::<A, D>(data, Some(weights))
+    }
+
+    pub(crate) fn estimate_with<A: LibData, D: DistanceMeasure<A>>(
+        &self,
+        data: ArrayView2<A>,
+        weights: Option<ArrayView1<A>>,
+    ) -> Result<A> {
+        self.estimate_sharing::<A, D>(data, weights, None)
+            .map(|(bandwidth, _)| bandwidth)
+    }
+
+    /// Estimates the bandwidth like `estimate_with`. If `KnnQuantile` compares all rows with
+    /// each other, it also returns the distance of every row to its `adaptive_k`-th nearest
+    /// neighbor from the same distances.
+    pub(crate) fn estimate_sharing<A: LibData, D: DistanceMeasure<A>>(
+        &self,
+        data: ArrayView2<A>,
+        weights: Option<ArrayView1<A>>,
+        adaptive_k: Option<usize>,
+    ) -> Result<(A, Option<Vec<A>>)> {
+        self.validate()?;
+        if data.is_empty() {
+            return Err(MeanShiftError::EmptyInput);
+        }
+        let n_rows = weights.map_or_else(|| A::from_usize(data.shape()[0]).unwrap(), |w| w.sum());
+        let d = A::from_usize(data.shape()[1]).unwrap();
+        let sigma = || {
+            let variance = data
+                .axis_iter(Axis(1))
+                .map(|column| standard_deviation(column, weights).powi(2))
+                .sum::<A>()
+                / d;
+            variance.sqrt()
+        };
+
+        let (bandwidth, kth_distances) = match self {
+            Self::KnnQuantile {
+                quantile,
+                n_samples,
+                seed,
+            } => knn_quantile_distances::<A, D>(
+                data, weights, *quantile, *n_samples, *seed, adaptive_k,
+            )?,
+            Self::Scott => (sigma() * scott_factor(n_rows, d), None),
+            Self::Silverman => (sigma() * silverman_factor(n_rows, d), None),
+            Self::MedianPairwise { n_samples, seed } => (
+                median_pairwise_bandwidth::<A, D>(data, weights, *n_samples, *seed)?,
+                None,
+            ),
+        };
+        validate_bandwidth(bandwidth)?;
+        Ok((bandwidth, kth_distances))
+    }
+}
+
+/// Estimates the bandwidth like sklearn's `estimate_bandwidth`: draws `n_samples` rows with an
+/// RNG seeded by `seed` (all rows if `None`) and averages the distance of every drawn row to its
+/// `quantile * n_samples`-th nearest drawn row, counting the row itself.
+/// Works with any distance measure, since it compares all pairs of drawn rows.
+pub fn estimate_bandwidth<A: LibData, D: DistanceMeasure<A>>(
+    data: ArrayView2<A>,
+    quantile: f64,
+    n_samples: Option<usize>,
+    seed: u64,
+) -> Result<A> {
+    validate_quantile(quantile)?;
+    validate_n_samples(n_samples)?;
+    if data.is_empty() {
+        return Err(MeanShiftError::EmptyInput);
+    }
+    knn_quantile_bandwidth::<A, D>(data, None, quantile, n_samples, seed)
+}
+
+/// With weights, the neighbors of a row are counted with their weights until they reach
+/// `quantile` of the total weight, and the distances are averaged with the weights of the rows.
+fn knn_quantile_bandwidth<A: LibData, D: DistanceMeasure<A>>(
+    data: ArrayView2<A>,
+    weights: Option<ArrayView1<A>>,
+    quantile: f64,
+    n_samples: Option<usize>,
+    seed: u64,
+) -> Result<A> {
+    knn_quantile_distances::<A, D>(data, weights, quantile, n_samples, seed, None)
+        .map(|(bandwidth, _)| bandwidth)
+}
+
+/// Like `knn_quantile_bandwidth`, but if all rows are drawn, additionally takes the distance of
+/// every row to its `adaptive_k`-th nearest neighbor besides itself, counted with the weights,
+/// from the same distances.
+fn knn_quantile_distances<A: LibData, D: DistanceMeasure<A>>(
+    data: ArrayView2<A>,
+    weights: Option<ArrayView1<A>>,
+    quantile: f64,
+    n_samples: Option<usize>,
+    seed: u64,
+    adaptive_k: Option<usize>,
+) -> Result<(A, Option<Vec<A>>)> {
+    let all_rows = n_samples.is_none_or(|n_samples| n_samples >= data.shape()[0]);
+    let adaptive_k = adaptive_k.filter(|_| all_rows);
+    let (data, weights) = subsample(data, weights, n_samples, seed);
+    let rows: Vec<&[A]> = data
+        .outer_iter()
+        .map(|row| row.to_slice().unwrap())
+        .collect();
+    let n = rows.len();
+
+    let distances_of = |row: &[A]| -> Vec<A> {
+        rows.iter()
+            .map(|other| D::distance_slice(row, other))
+            .collect()
+    };
+    let (sum, total, kth_distances) = match weights {
+        None => {
+            let k = ((n as f64 * quantile) as usize).clamp(1, n);
+            let distances: Vec<(A, Option<A>)> = rows
+                .par_iter()
+                .map(|row| {
+                    let mut distances = distances_of(row);
+                    let quantile_distance = kth_distance(&mut distances, k);
+                    // the nearest neighbor of every row is the row itself
+                    let adaptive = adaptive_k.map(|k| kth_distance(&mut distances, (k + 1).min(n)));
+                    (quantile_distance, adaptive)
+                })
+                .collect();
+            let sum: A = distances.iter().map(|(d, _)| *d).sum();
+            let adaptive = distances.into_iter().map(|(_, d)| d).collect();
+            (sum, A::from_usize(n).unwrap(), adaptive)
+        }
+        Some(weights) => {
+            let total = weights.sum();
+            let threshold = neighbor_weight_threshold(total, quantile);
+            let distances: Vec<(A, Option<A>)> = rows
+                .par_iter()
+                .enumerate()
+                .map(|(i, row)| {
+                    let mut pairs: Vec<(A, A)> = distances_of(row)
+                        .into_iter()
+                        .zip(weights.iter().copied())
+                        .collect();
+                    let quantile_distance = weighted_quantile(&mut pairs, threshold) * weights[i];
+                    let adaptive = adaptive_k
+                        .map(|k| weighted_quantile(&mut pairs, A::from_usize(k + 1).unwrap()));
+                    (quantile_distance, adaptive)
+                })
+                .collect();
+            let sum: A = distances.iter().map(|(d, _)| *d).sum();
+            let adaptive = distances.into_iter().map(|(_, d)| d).collect();
+            (sum, total, adaptive)
+        }
+    };
+    Ok((sum / total, kth_distances))
+}
+
+/// Average distance of every row of a precomputed distance matrix to its `quantile` nearest
+/// neighbor, like `BandwidthEstimator::KnnQuantile` without subsampling.
+pub(crate) fn knn_quantile_of_distances<A: LibData>(
+    distances: ArrayView2<A>,
+    quantile: f64,
+) -> Result<A> {
+    validate_quantile(quantile)?;
+    let n = distances.shape()[0];
+    let k = ((n as f64 * quantile) as usize).clamp(1, n);
+    let sum: A = distances
+        .axis_iter(Axis(0))
+        .into_par_iter()
+        .map(|row| kth_distance(&mut row.to_vec(), k))
+        .sum();
+    let bandwidth = sum / A::from_usize(n).unwrap();
+    validate_bandwidth(bandwidth)?;
+    Ok(bandwidth)
+}
+
+fn kth_distance<A: LibData>(distances: &mut [A], k: usize) -> A {
+    let (_, kth_distance, _) = distances.select_nth_unstable_by(k - 1, compare_distances);
+    *kth_distance
+}
+
+/// Median distance between pairs of drawn rows. With weights, a pair of rows counts
+/// `weights[i] * weights[j]` times and every row pairs `weights[i] * (weights[i] - 1) / 2` times
+/// with itself.
+fn median_pairwise_bandwidth<A: LibData, D: DistanceMeasure<A>>(
+    data: ArrayView2<A>,
+    weights: Option<ArrayView1<A>>,
+    n_samples: Option<usize>,
+    seed: u64,
+) -> Result<A> {
+    let (data, weights) = subsample(data, weights, n_samples, seed);
+    let rows: Vec<&[A]> = data
+        .outer_iter()
+        .map(|row| row.to_slice().unwrap())
+        .collect();
+    let weight = |i: usize| weights.as_ref().map_or(A::one(), |w| w[i]);
+
+    let mut pairs: Vec<(A, A)> = (0..rows.len())
+        .into_par_iter()
+        .flat_map_iter(|i| {
+            let rows = &rows;
+            (i + 1..rows.len())
+                .map(move |j| (D::distance_slice(rows[i], rows[j]), weight(i) * weight(j)))
+        })
+        .collect();
+    if weights.is_some() {
+        let half = A::from_f32(0.5).unwrap();
+        pairs.extend((0..rows.len()).map(|i| {
+            (
+                A::zero(),
+                (weight(i) * (weight(i) - A::one()) * half).max(A::zero()),
+            )
+        }));
+    }
+    let total: A = pairs.iter().map(|(_, w)| *w).sum();
+    if pairs.is_empty() || total <= A::zero() {
+        return Err(MeanShiftError::EmptyInput);
+    }
+
+    if weights.is_none() {
+        let mut distances: Vec<A> = pairs.into_iter().map(|(d, _)| d).collect();
+        let middle = distances.len() / 2;
+        let (_, median, _) = distances.select_nth_unstable_by(middle, compare_distances);
+        return Ok(*median);
+    }
+    // the first distance past half of the total weight, like the upper median without weights
+    pairs.sort_by(|(a, _), (b, _)| compare_distances(a, b));
+    let half = total / A::from_f32(2.0).unwrap();
+    let mut cumulative = A::zero();
+    for (distance, weight) in pairs.iter() {
+        cumulative = cumulative + *weight;
+        if cumulative > half {
+            return Ok(*distance);
+        }
+    }
+    Ok(pairs.last().unwrap().0)
+}
+
+/// The smallest distance at which the weights of the closer pairs reach `threshold`.
+fn weighted_quantile<A: LibData>(pairs: &mut [(A, A)], threshold: A) -> A {
+    pairs.sort_by(|(a, _), (b, _)| compare_distances(a, b));
+    let mut cumulative = A::zero();
+    for (distance, weight) in pairs.iter() {
+        cumulative = cumulative + *weight;
+        if cumulative >= threshold {
+            return *distance;
+        }
+    }
+    pairs.last().unwrap().0
+}
+
+/// Weight of the nearest neighbors to consider, `floor(quantile * total)` but at least one, which
+/// gives the `quantile * n`-th nearest neighbor without weights.
+fn neighbor_weight_threshold<A: LibData>(total: A, quantile: f64) -> A {
+    (total * A::from_f64(quantile).unwrap())
+        .floor()
+        .max(A::one())
+        .min(total)
+}
+
+fn compare_distances<A: LibData>(a: &A, b: &A) -> Ordering {
+    a.partial_cmp(b).unwrap_or(Ordering::Equal)
+}
+
+/// `n_samples` distinct rows of `data` and their weights drawn with an RNG seeded by `seed`, or
+/// all of them, in standard layout. All rows are only copied if they are not in standard layout.
+fn subsample<'a, 'b, A: LibData>(
+    data: ArrayView2<'a, A>,
+    weights: Option<ArrayView1<'b, A>>,
+    n_samples: Option<usize>,
+    seed: u64,
+) -> (CowArray<'a, A, Ix2>, Option<CowArray<'b, A, Ix1>>) {
+    match n_samples {
+        Some(n_samples) if n_samples < data.shape()[0] => {
+            let mut indices =
+                sample(&mut StdRng::seed_from_u64(seed), data.shape()[0], n_samples).into_vec();
+            indices.sort_unstable();
+            (
+                CowArray::from(data.select(Axis(0), &indices)),
+                weights.map(|w| CowArray::from(w.select(Axis(0), &indices))),
+            )
+        }
+        _ if data.is_standard_layout() => (CowArray::from(data), weights.map(CowArray::from)),
+        _ => (
+            CowArray::from(data.as_standard_layout().into_owned()),
+            weights.map(CowArray::from),
+        ),
+    }
+}
+
+/// How the bandwidth extends along the dimensions of the data.
+///
+/// A bandwidth of `h_j` per column corresponds to the bandwidth matrix `diag(h_j²)`. With a
+/// bandwidth matrix `H = L Lᵀ`, the data is clustered in the coordinates `z = L⁻¹ x`, in which
+/// `H` becomes the identity, so the neighbor search and the kernel weights both follow `H`.
+/// There, the scalar bandwidth of `MeanShift` scales `H` and defaults to one. Since the
+/// coordinates are transformed linearly, this suits vector space measures like Euclidean
+/// and Manhattan.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum Anisotropy<A: LibData> {
+    /// All dimensions share the scalar bandwidth.
+    #[default]
+    Isotropic,
+    /// One bandwidth per column.
+    PerDimension(Array1<A>),
+    /// A symmetric positive definite bandwidth matrix.
+    Matrix(Array2<A>),
+    /// One bandwidth per column, estimated from the data.
+    Estimated(DimensionEstimator),
+}
+
+/// Rules that estimate the bandwidth of every column on its own.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DimensionEstimator {
+    /// Scott's rule `σ n^(-1 / (d + 4))`.
+    Scott,
+    /// Silverman's rule `σ (4 / ((d + 2) n))^(1 / (d + 4))`.
+    Silverman,
+    /// Average distance of every value to its `quantile * n`-th nearest value in the column.
+    KnnQuantile { quantile: f64 },
+}
+
+impl<A: LibData> Anisotropy<A> {
+    /// Checks the parts that do not depend on the data.
+    pub(crate) fn validate(&self) -> std::result::Result<(), ParameterError> {
+        match self {
+            Self::PerDimension(bandwidths) => bandwidths
+                .iter()
+                .try_for_each(|bandwidth| validate_bandwidth(*bandwidth)),
+            Self::Estimated(DimensionEstimator::KnnQuantile { quantile }) => {
+                validate_quantile(*quantile)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The transformation into the coordinates in which the bandwidth is isotropic, or `None`
+    /// if it already is.
+    pub(crate) fn whitening(
+        &self,
+        data: ArrayView2<A>,
+        weights: Option<ArrayView1<A>>,
+    ) -> Result<Option<Whitening<A>>> {
+        let columns = data.shape()[1];
+        match self {
+            Self::Isotropic => Ok(None),
+            Self::PerDimension(bandwidths) => {
+                if bandwidths.len() != columns {
+                    return Err(MeanShiftError::DimensionMismatch {
+                        expected: columns,
+                        actual: bandwidths.len(),
+                    });
+                }
+                Ok(Some(Whitening::Diagonal(bandwidths.clone())))
+            }
+            Self::Matrix(matrix) => {
+                if matrix.shape() != [columns, columns] {
+                    return Err(MeanShiftError::DimensionMismatch {
+                        expected: columns,
+                        actual: matrix.shape()[0].max(matrix.shape()[1]),
+                    });
+                }
+                let lower = cholesky(matrix.view()).ok_or(ParameterError::NotPositiveDefinite)?;
+                let inverse = invert_lower(lower.view());
+                Ok(Some(Whitening::Cholesky { lower, inverse }))
+            }
+            Self::Estimated(estimator) => Ok(Some(Whitening::Diagonal(
+                estimator.estimate_with(data, weights)?,
+            ))),
+        }
+    }
+}
+
+impl DimensionEstimator {
+    /// Estimates the bandwidth of every column of `data`. Constant columns get a bandwidth of one.
+    pub fn estimate<A: LibData>(&self, data: ArrayView2<A>) -> Result<Array1<A>> {
+        self.estimate_with(data, None)
+    }
+
+    /// Estimates the bandwidth of every column of `data`, counting every row `weights[i]` times.
+    pub fn estimate_weighted<A: LibData>(
+        &self,
+        data: ArrayView2<A>,
+        weights: ArrayView1<A>,
+    ) -> Result<Array1<A>> {
+        validate_weights(weights, data.shape()[0])?;
+        self.estimate_with(data, Some(weights))
+    }
+
+    pub(crate) fn estimate_with<A: LibData>(
+        &self,
+        data: ArrayView2<A>,
+        weights: Option<ArrayView1<A>>,
+    ) -> Result<Array1<A>> {
+        if data.is_empty() {
+            return Err(MeanShiftError::EmptyInput);
+        }
+        let n_rows = weights.map_or_else(|| A::from_usize(data.shape()[0]).unwrap(), |w| w.sum());
+        let d = A::from_usize(data.shape()[1]).unwrap();
+
+        let bandwidths: Vec<A> = match self {
+            Self::Scott => {
+                let factor = scott_factor(n_rows, d);
+                data.axis_iter(Axis(1))
+                    .map(|column| standard_deviation(column, weights) * factor)
+                    .collect()
+            }
+            Self::Silverman => {
+                let factor = silverman_factor(n_rows, d);
+                data.axis_iter(Axis(1))
+                    .map(|column| standard_deviation(column, weights) * factor)
+                    .collect()
+            }
+            Self::KnnQuantile { quantile } => {
+                validate_quantile(*quantile)?;
+                let threshold = neighbor_weight_threshold(n_rows, *quantile);
+                data.axis_iter(Axis(1))
+                    .into_par_iter()
+                    .map(|column| mean_knn_distance(column, weights, threshold))
+                    .collect()
+            }
+        };
+
+        Ok(bandwidths
+            .into_iter()
+            .map(|bandwidth| {
+                if bandwidth > A::zero() && bandwidth.is_finite() {
+                    bandwidth
+                } else {
+                    A::one()
+                }
+            })
+            .collect())
+    }
+}
+
+/// Linear map into the coordinates in which the bandwidth matrix is the identity.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Whitening<A: LibData> {
+    /// Divides every column by its bandwidth.
+    Diagonal(Array1<A>),
+    /// Multiplies by the inverse of the Cholesky factor of the bandwidth matrix.
+    Cholesky {
+        lower: Array2<A>,
+        inverse: Array2<A>,
+    },
+}
+
+impl<A: LibData> Whitening<A> {
+    /// The bandwidth matrix `H`.
+    pub(crate) fn matrix(&self) -> Array2<A> {
+        match self {
+            Self::Diagonal(bandwidths) => Array2::from_diag(&bandwidths.mapv(|h| h * h)),
+            Self::Cholesky { lower, .. } => lower.dot(&lower.t()),
+        }
+    }
+
+    pub(crate) fn whiten(&self, data: ArrayView2<A>) -> Array2<A> {
+        match self {
+            Self::Diagonal(bandwidths) => &data / bandwidths,
+            Self::Cholesky { inverse, .. } => data.dot(&inverse.t()),
+        }
+    }
+
+    pub(crate) fn unwhiten(&self, data: ArrayView2<A>) -> Array2<A> {
+        match self {
+            Self::Diagonal(bandwidths) => &data * bandwidths,
+            Self::Cholesky { lower, .. } => data.dot(&lower.t()),
+        }
+    }
+}
+
+pub(crate) fn validate_quantile(quantile: f64) -> std::result::Result<(), ParameterError> {
+    if !(quantile > 0.0 && quantile <= 1.0) {
+        return Err(ParameterError::InvalidQuantile(quantile));
+    }
+    Ok(())
+}
+
+fn validate_n_samples(n_samples: Option<usize>) -> std::result::Result<(), ParameterError> {
+    if n_samples == Some(0) {
+        return Err(ParameterError::NSamplesZero);
+    }
+    Ok(())
+}
+
+fn scott_factor<A: LibData>(n: A, d: A) -> A {
+    n.powf(-(d + A::from_f32(4.0).unwrap()).recip())
+}
+
+fn silverman_factor<A: LibData>(n: A, d: A) -> A {
+    let four = A::from_f32(4.0).unwrap();
+    (four / ((d + A::from_f32(2.0).unwrap()) * n)).powf((d + four).recip())
+}
+
+/// Sample standard deviation of the values of `column` that are not NaN, counting every value
+/// with its weight.
+fn standard_deviation<A: LibData>(column: ArrayView1<A>, weights: Option<ArrayView1<A>>) -> A {
+    let values: Vec<(A, A)> = column
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| !x.is_nan())
+        .map(|(i, x)| (*x, weights.map_or(A::one(), |w| w[i])))
+        .collect();
+    let n: A = values.iter().map(|(_, w)| *w).sum();
+    if n <= A::one() {
+        return A::zero();
+    }
+    let mean = values.iter().map(|(x, w)| *x * *w).sum::<A>() / n;
+    let squares: A = values.iter().map(|(x, w)| (*x - mean).powi(2) * *w).sum();
+    (squares / (n - A::one())).sqrt()
+}
+
+/// Average distance of every value to the farthest of its nearest values that together weigh
+/// `threshold`, counting the value itself. Without weights, this is the `threshold`-th nearest
+/// value.
+fn mean_knn_distance<A: LibData>(
+    column: ArrayView1<A>,
+    weights: Option<ArrayView1<A>>,
+    threshold: A,
+) -> A {
+    let mut sorted: Vec<(A, A)> = column
+        .iter()
+        .enumerate()
+        .map(|(i, x)| (*x, weights.map_or(A::one(), |w| w[i])))
+        .collect();
+    sorted.sort_by(|(a, _), (b, _)| compare_distances(a, b));
+    let n = sorted.len();
+    let mut prefix = vec![A::zero(); n + 1];
+    for (i, (_, weight)) in sorted.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + *weight;
+    }
+    // the nearest values of `sorted[i]` form a window `[left, right]` around it, whose ends only
+    // ever move to the right
+    let weight_of = |left: usize, right: usize| prefix[right + 1] - prefix[left];
+    let extend = |left: usize, mut right: usize| {
+        while right + 1 < n && weight_of(left, right) < threshold {
+            right += 1;
+        }
+        right
+    };
+
+    let mut left = 0;
+    let mut right = 0;
+    let mut sum = A::zero();
+    for (i, (x, weight)) in sorted.iter().enumerate() {
+        right = extend(left, right.max(i));
+        let radius = |left: usize, right: usize| (*x - sorted[left].0).max(sorted[right].0 - *x);
+        while left < i {
+            let next_right = extend(left + 1, right);
+            if weight_of(left + 1, next_right) < threshold
+                || radius(left + 1, next_right) > radius(left, right)
+            {
+                break;
+            }
+            left += 1;
+            right = next_right;
+        }
+        sum = sum + radius(left, right) * *weight;
+    }
+    sum / prefix[n]
+}
+
+/// Lower triangular `L` with `matrix = L Lᵀ`, or `None` if `matrix` is not symmetric positive
+/// definite.
+fn cholesky<A: LibData>(matrix: ArrayView2<A>) -> Option<Array2<A>> {
+    if matrix.iter().any(|x| !x.is_finite()) {
+        return None;
+    }
+    let n = matrix.shape()[0];
+    let scale = matrix.fold(A::zero(), |max, x| max.max(x.abs()));
+    let tolerance = scale * A::epsilon().sqrt();
+    let mut lower = Array2::zeros((n, n));
+    for i in 0..n {
+        for j in 0..=i {
+            if (matrix[[i, j]] - matrix[[j, i]]).abs() > tolerance {
+                return None;
+            }
+            let mut sum = matrix[[i, j]];
+            for k in 0..j {
+                sum = sum - lower[[i, k]] * lower[[j, k]];
+            }
+            if i == j {
+                if sum <= A::zero() {
+                    return None;
+                }
+                lower[[i, i]] = sum.sqrt();
+            } else {
+                lower[[i, j]] = sum / lower[[j, j]];
+            }
+        }
+    }
+    Some(lower)
+}
+
+/// Inverse of a lower triangular matrix with a positive diagonal, by forward substitution.
+fn invert_lower<A: LibData>(lower: ArrayView2<A>) -> Array2<A> {
+    let n = lower.shape()[0];
+    let mut inverse = Array2::zeros((n, n));
+    for j in 0..n {
+        for i in j..n {
+            let mut sum = if i == j { A::one() } else { A::zero() };
+            for k in j..i {
+                sum = sum - lower[[i, k]] * inverse[[k, j]];
+            }
+            inverse[[i, j]] = sum / lower[[i, i]];
+        }
+    }
+    inverse
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distance_measure::{Euclidean, DTW};
+    use crate::test_utils::{close_l1, read_data};
+    use ndarray::{arr1, arr2, Zip};
+
+    #[test]
+    fn test_matrix_whitening() {
+        let matrix = arr2(&[[4.0, 1.0], [1.0, 2.0]]);
+        let whitening = Anisotropy::Matrix(matrix.clone())
+            .whitening(arr2(&[[0.0, 0.0]]).view(), None)
+            .unwrap()
+            .unwrap();
+        let data = arr2(&[[1.0, 2.0], [-3.0, 0.5]]);
+
+        let whitened = whitening.whiten(data.view());
+        // |L⁻¹ x|² equals the Mahalanobis form xᵀ H⁻¹ x
+        let x = data.row(0);
+        let mahalanobis = (x[0] * x[0] * 2.0 - 2.0 * x[0] * x[1] + x[1] * x[1] * 4.0) / 7.0;
+        close_l1(mahalanobis, whitened.row(0).dot(&whitened.row(0)), 1e-12);
+        let unwhitened = whitening.unwhiten(whitened.view());
+        Zip::from(&unwhitened)
+            .and(&data)
+            .for_each(|a, b| close_l1(*a, *b, 1e-12));
+        Zip::from(&whitening.matrix())
+            .and(&matrix)
+            .for_each(|a, b| close_l1(*a, *b, 1e-12));
+    }
+
+    #[test]
+    fn test_invalid_anisotropy() {
+        let data = arr2(&[[0.0, 0.0], [1.0, 1.0]]);
+        assert_eq!(
+            Anisotropy::Matrix(arr2(&[[1.0, 2.0], [2.0, 1.0]])).whitening(data.view(), None),
+            Err(MeanShiftError::InvalidParameter(
+                ParameterError::NotPositiveDefinite
+            ))
+        );
+        assert_eq!(
+            Anisotropy::PerDimension(arr1(&[1.0])).whitening(data.view(), None),
+            Err(MeanShiftError::DimensionMismatch {
+                expected: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            Anisotropy::PerDimension(arr1(&[1.0, 0.0])).validate(),
+            Err(ParameterError::InvalidBandwidth(0.0))
+        );
+    }
+
+    #[test]
+    fn test_dimension_estimators() {
+        let data = arr2(&[
+            [0.0, 0.0, 5.0],
+            [1.0, 10.0, 5.0],
+            [2.0, 20.0, 5.0],
+            [3.0, 30.0, 5.0],
+        ]);
+        let std = (5.0f64 / 3.0).sqrt();
+
+        let scott = DimensionEstimator::Scott.estimate(data.view()).unwrap();
+        let factor = 4f64.powf(-1.0 / 7.0);
+        close_l1(std * factor, scott[0], 1e-12);
+        close_l1(std * factor * 10.0, scott[1], 1e-12);
+        // constant columns keep a bandwidth of one
+        assert_eq!(1.0, scott[2]);
+
+        let silverman = DimensionEstimator::Silverman.estimate(data.view()).unwrap();
+        close_l1(std * (4.0f64 / 20.0).powf(1.0 / 7.0), silverman[0], 1e-12);
+
+        let knn = DimensionEstimator::KnnQuantile { quantile: 0.5 }
+            .estimate(data.view())
+            .unwrap();
+        assert_eq!(arr1(&[1.0, 10.0, 1.0]), knn);
+    }
+
+    #[test]
+    fn test_estimate_bandwidth() {
+        let data = arr2(&[[0.0], [1.0], [2.0], [3.0]]);
+        assert_eq!(
+            1.0,
+            estimate_bandwidth::<_, Euclidean>(data.view(), 0.5, None, 0).unwrap()
+        );
+        assert_eq!(
+            Err(MeanShiftError::InvalidParameter(
+                ParameterError::InvalidQuantile(0.0)
+            )),
+            estimate_bandwidth::<_, Euclidean>(data.view(), 0.0, None, 0)
+        );
+
+        let data: Array2<f64> = read_data("data/test.csv");
+        assert_eq!(
+            BandwidthEstimator::default()
+                .estimate::<_, Euclidean>(data.view())
+                .unwrap(),
+            estimate_bandwidth::<_, Euclidean>(data.view(), 0.3, None, 0).unwrap()
+        );
+        let sampled = estimate_bandwidth::<_, Euclidean>(data.view(), 0.3, Some(50), 7).unwrap();
+        assert_eq!(
+            sampled,
+            estimate_bandwidth::<_, Euclidean>(data.view(), 0.3, Some(50), 7).unwrap()
+        );
+        close_l1(
+            estimate_bandwidth::<_, Euclidean>(data.view(), 0.3, None, 0).unwrap(),
+            sampled,
+            0.1,
+        );
+        assert!(estimate_bandwidth::<_, DTW>(data.view(), 0.3, Some(20), 7).unwrap() > 0.0);
+    }
+
+    #[test]
+    fn test_bandwidth_estimators() {
+        let data = arr2(&[[0.0], [1.0], [2.0], [3.0]]);
+        let median = BandwidthEstimator::MedianPairwise {
+            n_samples: None,
+            seed: 0,
+        };
+        assert_eq!(2.0, median.estimate::<_, Euclidean>(data.view()).unwrap());
+
+        let std = (5.0f64 / 3.0).sqrt();
+        close_l1(
+            std * 4f64.powf(-0.2),
+            BandwidthEstimator::Scott
+                .estimate::<_, Euclidean>(data.view())
+                .unwrap(),
+            1e-12,
+        );
+        close_l1(
+            std * (4.0f64 / 12.0).powf(0.2),
+            BandwidthEstimator::Silverman
+                .estimate::<_, Euclidean>(data.view())
+                .unwrap(),
+            1e-12,
+        );
+        assert_eq!(
+            Err(MeanShiftError::InvalidParameter(
+                ParameterError::InvalidBandwidth(0.0)
+            )),
+            BandwidthEstimator::Scott.estimate::<_, Euclidean>(arr2(&[[1.0], [1.0]]).view())
+        );
+    }
+
+    #[test]
+    fn test_weighted_estimators_count_multiplicities() {
+        let unique = arr2(&[[0.0], [1.0], [2.0], [3.0], [7.0]]);
+        let weights = arr1(&[2.0, 1.0, 3.0, 1.0, 1.0]);
+        let repeated = arr2(&[[0.0], [0.0], [1.0], [2.0], [2.0], [2.0], [3.0], [7.0]]);
+
+        for estimator in [
+            BandwidthEstimator::default(),
+            BandwidthEstimator::Scott,
+            BandwidthEstimator::MedianPairwise {
+                n_samples: None,
+                seed: 0,
+            },
+        ] {
+            close_l1(
+                estimator.estimate::<_, Euclidean>(repeated.view()).unwrap(),
+                estimator
+                    .estimate_weighted::<_, Euclidean>(unique.view(), weights.view())
+                    .unwrap(),
+                1e-12,
+            );
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use crate::error::Result;
+use crate::params::{validate_bandwidth, ParameterError};
+use crate::result::ClusteringResult;
+use crate::utils::LibData;
+use ndarray::{Array1, Array2, ArrayView1, ArrayView2, Axis};
+use std::collections::HashMap;
+
+/// Pre-pass that collapses duplicate rows into one row each before clustering.
+///
+/// Every unique row is clustered once, weighted with the summed weights of the rows it stands
+/// for, and the labels are expanded back to the original rows. The per-seed statistics and the
+/// support of the result refer to the unique rows.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum Deduplication<A: LibData> {
+    /// Cluster every row.
+    #[default]
+    Off,
+    /// Collapse rows with exactly the same values into the first of them.
+    Exact,
+    /// Collapse rows that fall into the same cell of a grid with cells of `cell_size` along
+    /// every dimension into their weighted mean.
+    Grid { cell_size: A },
+}
+
+/// Unique rows, their weights and the unique row of every original row.
+pub(crate) struct Compressed<A: LibData> {
+    pub rows: Array2<A>,
+    pub weights: Array1<A>,
+    pub inverse: Vec<usize>,
+}
+
+impl<A: LibData> Deduplication<A> {
+    pub(crate) fn validate(&self) -> std::result::Result<(), ParameterError> {
+        match self {
+            Self::Grid { cell_size } => validate_bandwidth(*cell_size).map_err(|_| {
+                ParameterError::InvalidCellSize(cell_size.to_f64().unwrap_or(f64::NAN))
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Collapses the duplicates of `data`, or returns `None` if deduplication is off.
+    pub(crate) fn compress(
+        &self,
+        data: ArrayView2<A>,
+        weights: Option<ArrayView1<A>>,
+    ) -> Result<Option<Compressed<A>>> {
+        self.validate()?;
+        let key: Box<dyn Fn(&A) -> u64> = match self {
+            Self::Off => return Ok(None),
+            Self::Exact => Box::new(|x: &A| exact_key(*x)),
+            Self::Grid { cell_size } => {
+                let cell_size = *cell_size;
+                Box::new(move |x: &A| grid_key(*x, cell_size))
+            }
+        };
+
+        let mut unique: HashMap<Vec<u64>, usize> = HashMap::new();
+        let mut first_rows = vec![];
+        let mut inverse = Vec::with_capacity(data.shape()[0]);
+        for (i, row) in data.axis_iter(Axis(0)).enumerate() {
+            let next = unique.len();
+            let u = *unique.entry(row.iter().map(&key).collect()).or_insert(next);
+            if u == next {
+                first_rows.push(i);
+            }
+            inverse.push(u);
+        }
+
+        let mut counts: Array1<A> = Array1::zeros(first_rows.len());
+        for (i, u) in inverse.iter().enumerate() {
+            counts[*u] = counts[*u] + weights.map_or(A::one(), |w| w[i]);
+        }
+        let rows = match self {
+            Self::Grid { .. } => {
+                let mut sums: Array2<A> = Array2::zeros((first_rows.len(), data.shape()[1]));
+                for ((i, row), u) in data.axis_iter(Axis(0)).enumerate().zip(inverse.iter()) {
+                    let weight = weights.map_or(A::one(), |w| w[i]);
+                    let mut sum = sums.row_mut(*u);
+                    sum.zip_mut_with(&row, |s, x| *s = *s + *x * weight);
+                }
+                for (mut sum, count) in sums.axis_iter_mut(Axis(0)).zip(counts.iter()) {
+                    if *count > A::zero() {
+                        sum.mapv_inplace(|s| s / *count);
+                    }
+                }
+                // cells of rows with zero weight keep their first row
+                for (u, first) in first_rows.iter().enumerate() {
+                    if counts[u] <= A::zero() {
+                        sums.row_mut(u).assign(&data.row(*first));
+                    }
+                }
+                sums
+            }
+            _ => data.select(Axis(0), &first_rows),
+        };
+
+        Ok(Some(Compressed {
+            rows,
+            weights: counts,
+            inverse,
+        }))
+    }
+}
+
+impl<A: LibData> Compressed<A> {
+    /// Maps the per-row parts of a result over the unique rows back to the original rows.
+    pub(crate) fn expand(&self, mut result: ClusteringResult<A>) -> ClusteringResult<A> {
+        result.labels = self.inverse.iter().map(|u| result.labels[*u]).collect();
+        result.point_bandwidths = result
+            .point_bandwidths
+            .map(|bandwidths| self.inverse.iter().map(|u| bandwidths[*u]).collect());
+        result
+    }
+}
+
+/// Bit pattern of `x` that is equal for equal values, including `0.0` and `-0.0` and all NaNs.
+fn exact_key<A: LibData>(x: A) -> u64 {
+    if x.is_nan() {
+        u64::MAX
+    } else if x == A::zero() {
+        0
+    } else {
+        x.to_f64().unwrap().to_bits()
+    }
+}
+
+fn grid_key<A: LibData>(x: A, cell_size: A) -> u64 {
+    if x.is_nan() {
+        u64::MAX
+    } else {
+        (x / cell_size).floor().to_i64().unwrap_or(i64::MAX) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::close_l1;
+    use ndarray::{arr1, arr2};
+
+    #[test]
+    fn test_exact_deduplication() {
+        let data = arr2(&[[1.0, 2.0], [0.0, 1.0], [1.0, 2.0], [-0.0, 1.0], [3.0, 3.0]]);
+        let compressed = Deduplication::Exact
+            .compress(data.view(), None)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(compressed.rows, arr2(&[[1.0, 2.0], [0.0, 1.0], [3.0, 3.0]]));
+        assert_eq!(compressed.weights, arr1(&[2.0, 2.0, 1.0]));
+        assert_eq!(compressed.inverse, vec![0, 1, 0, 1, 2]);
+    }
+
+    #[test]
+    fn test_grid_deduplication() {
+        let data = arr2(&[[0.1, 0.1], [0.3, 0.2], [1.5, 0.1]]);
+        let weights = arr1(&[1.0, 3.0, 2.0_f64]);
+        let compressed = Deduplication::Grid { cell_size: 1.0 }
+            .compress(data.view(), Some(weights.view()))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(compressed.weights, arr1(&[4.0, 2.0]));
+        assert_eq!(compressed.inverse, vec![0, 0, 1]);
+        close_l1(0.25, compressed.rows[[0, 0]], 1e-12);
+        close_l1(0.175, compressed.rows[[0, 1]], 1e-12);
+        assert_eq!(compressed.rows.row(1), data.row(2));
+        assert!(Deduplication::<f64>::Off
+            .compress(data.view(), None)
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,62 @@
+use crate::distance_measure::{DistanceMeasure, Euclidean};
+use crate::error::Result;
+use crate::neighbors::IndexBackend;
+use crate::utils::LibData;
+use ndarray::{Array2, ArrayView1, ArrayView2};
+
+/// Largest absolute difference along any dimension.
+#[derive(Copy, Clone, Default)]
+pub struct Chebyshev;
+
+impl<A: LibData> DistanceMeasure<A> for Chebyshev {
+    const NAME: &'static str = "chebyshev";
+    const INDEX_BACKENDS: &'static [IndexBackend] = &[
+        IndexBackend::KdTree,
+        IndexBackend::Grid,
+        IndexBackend::VpTree,
+        IndexBackend::Blocked,
+        IndexBackend::BruteForce,
+    ];
+
+    fn distance_slice(point_a: &[A], point_b: &[A]) -> A {
+        point_a
+            .iter()
+            .zip(point_b.iter())
+            .fold(A::zero(), |max, (a_, b_)| max.max(a_.sub(*b_).abs()))
+    }
+
+    fn distance(series_a: ArrayView2<A>, series_b: ArrayView2<A>) -> A {
+        series_a
+            .iter()
+            .zip(series_b.iter())
+            .fold(A::zero(), |max, (a_, b_)| max.max(a_.sub(*b_).abs()))
+    }
+
+    fn mean(points: Vec<ArrayView2<A>>) -> Result<Array2<A>> {
+        Euclidean::mean(points)
+    }
+
+    fn weighted_mean(points: Vec<ArrayView2<A>>, weights: ArrayView1<A>) -> Result<Array2<A>> {
+        Euclidean::weighted_mean(points, weights)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::distance_measure::Chebyshev;
+    use crate::DistanceMeasure;
+    use ndarray::{arr2, Axis};
+
+    #[test]
+    fn test_distance_is_same() {
+        let a = arr2(&[[0.0, 1.0, 2.0]]);
+        let b = arr2(&[[3.0, 5.0, 4.0]]);
+
+        let distance = Chebyshev::distance_slice(
+            a.index_axis(Axis(0), 0).as_slice().unwrap(),
+            b.index_axis(Axis(0), 0).as_slice().unwrap(),
+        );
+        assert_eq!(4.0, distance);
+        assert_eq!(distance, Chebyshev::distance(a.t(), b.t()))
+    }
+}
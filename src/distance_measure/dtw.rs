@@ -1,12 +1,16 @@
-use crate::utils::{nanmean, to_time_series_real_size, LibData};
+use crate::error::MeanShiftError;
+use crate::error::Result;
+use crate::utils::{contiguous, nanmean, to_time_series_real_size, LibData};
 use crate::{distance_measure::DistanceMeasure, utils::time_series_to_matrix};
-use anyhow::Result;
 use kdtree::distance::squared_euclidean;
 use log::*;
 use ndarray::{arr2, s, ArcArray, Array, Array1, Array2, ArrayView1, ArrayView2, Axis, Ix3};
+use std::cmp::Ordering;
 use std::ops::Mul;
 
 type ArcArray3<A> = ArcArray<A, Ix3>;
+/// Warping path of every series to the barycenter and the cost of these paths.
+type Assignment<A> = (Vec<Vec<(usize, usize)>>, A);
 
 #[derive(Copy, Clone, Default)]
 pub struct DTW;
@@ -27,8 +31,8 @@ impl DTW {
             for j in 0..len_b {
                 if mask[[i, j]].is_finite() {
                     cum_sum[[i + 1, j + 1]] = squared_euclidean(
-                        point_a.index_axis(Axis(0), i).as_slice().unwrap(),
-                        point_b.index_axis(Axis(0), j).as_slice().unwrap(),
+                        &contiguous(&point_a.index_axis(Axis(0), i)),
+                        &contiguous(&point_b.index_axis(Axis(0), j)),
                     );
                     cum_sum[[i + 1, j + 1]] = cum_sum[[i + 1, j + 1]]
                         + cum_sum[[i, j + 1]]
@@ -58,7 +62,7 @@ impl DTW {
                 match arr
                     .into_iter()
                     .enumerate()
-                    .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+                    .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
                     .unwrap()
                     .0
                 {
@@ -113,18 +117,19 @@ impl DTW {
         tol: A,
         weights: Option<Array1<A>>,
     ) -> Result<(Array2<A>, A)> {
-        let dataset = time_series_to_matrix(points);
+        let dataset = time_series_to_matrix(points)?;
         barycenter_size = barycenter_size.or_else(|| Some(dataset.shape()[1]));
         let weights = Self::set_weights(weights, dataset.shape()[0]);
-        let mut barycenter = init_barycenter.unwrap_or_else(|| {
-            Self::init_avg(dataset.to_shared(), barycenter_size.unwrap()).unwrap()
-        });
+        let mut barycenter = match init_barycenter {
+            Some(barycenter) => barycenter,
+            None => Self::init_avg(dataset.to_shared(), barycenter_size.unwrap())?,
+        };
         let mut cost_prev = A::max_value();
         let mut cost = A::max_value();
         for _i in 0..max_iter {
             let list_p_k;
             (list_p_k, cost) =
-                Self::mm_assignment(dataset.to_shared(), barycenter.view(), weights.view());
+                Self::mm_assignment(dataset.to_shared(), barycenter.view(), weights.view())?;
             let (diag_sum_v_k, list_w_k) =
                 Self::mm_valence_warping(list_p_k, barycenter_size.unwrap(), weights.view());
             barycenter = Self::mm_update_barycenter(dataset.to_shared(), diag_sum_v_k, list_w_k)?;
@@ -141,12 +146,33 @@ impl DTW {
         Ok((barycenter, cost))
     }
 
+    /// Average of the series, linearly resampled to `barycenter_size` if it has another length.
     fn init_avg<A: LibData>(dataset: ArcArray3<A>, barycenter_size: usize) -> Result<Array2<A>> {
-        if dataset.shape()[1] == barycenter_size {
-            nanmean(dataset.view(), Axis(0))
+        let avg = nanmean(dataset.view(), Axis(0))?;
+        let len = avg.shape()[0];
+        if len == barycenter_size {
+            return Ok(avg);
+        }
+        if len == 0 || barycenter_size == 0 {
+            return Err(MeanShiftError::EmptyInput);
+        }
+
+        let mut resampled = Array2::zeros([barycenter_size, avg.shape()[1]]);
+        let scale = if barycenter_size > 1 {
+            A::from_usize(len - 1).unwrap() / A::from_usize(barycenter_size - 1).unwrap()
         } else {
-            todo!()
+            A::zero()
+        };
+        for i in 0..barycenter_size {
+            let position = A::from_usize(i).unwrap() * scale;
+            let lower = position.floor().to_usize().unwrap().min(len - 1);
+            let upper = (lower + 1).min(len - 1);
+            let fraction = position - A::from_usize(lower).unwrap();
+            let row = &avg.index_axis(Axis(0), lower) * (A::one() - fraction)
+                + &avg.index_axis(Axis(0), upper) * fraction;
+            resampled.index_axis_mut(Axis(0), i).assign(&row);
         }
+        Ok(resampled)
     }
 
     fn set_weights<A: LibData>(weights: Option<Array1<A>>, n: usize) -> Array1<A> {
@@ -160,23 +186,21 @@ impl DTW {
         dataset: ArcArray3<A>,
         barycenter: ArrayView2<A>,
         weights: ArrayView1<A>,
-    ) -> (Vec<Vec<(usize, usize)>>, A) {
+    ) -> Result<Assignment<A>> {
         let n = dataset.shape()[0];
         let mut cost = A::from(0.0).unwrap();
         let mut list_p_k = vec![];
         for i in 0..n {
             let (path, dist_i) = Self::dtw_path(
                 barycenter,
-                to_time_series_real_size(dataset.index_axis(Axis(0), i))
-                    .unwrap()
-                    .view(),
+                to_time_series_real_size(dataset.index_axis(Axis(0), i))?.view(),
             );
             cost = cost + dist_i.powi(2).mul(weights[i]);
             list_p_k.push(path);
         }
 
         cost = cost.div(weights.sum());
-        (list_p_k, cost)
+        Ok((list_p_k, cost))
     }
 
     fn mm_valence_warping<A: LibData>(
@@ -213,11 +237,7 @@ impl DTW {
         let d = dataset.shape()[2];
         let barycenter_size = diag_sum_v_k.shape()[0];
         let mut sum_w_x = Array2::zeros([barycenter_size, d]);
-        for (_, (w_k, x_k)) in list_w_k
-            .into_iter()
-            .zip(dataset.axis_iter(Axis(0)))
-            .enumerate()
-        {
+        for (w_k, x_k) in list_w_k.into_iter().zip(dataset.axis_iter(Axis(0))) {
             sum_w_x = sum_w_x + w_k.dot(&to_time_series_real_size(x_k)?)
         }
         let into_diag = diag_sum_v_k
@@ -243,6 +263,7 @@ impl DTW {
 
 impl<A: LibData> DistanceMeasure<A> for DTW {
     const NAME: &'static str = "dtw";
+    const ALLOWS_NAN_PADDING: bool = true;
 
     fn distance_slice(point_a: &[A], point_b: &[A]) -> A {
         DTW::dtw_path(
@@ -267,6 +288,18 @@ impl<A: LibData> DistanceMeasure<A> for DTW {
             1,
         )
     }
+
+    fn weighted_mean(points: Vec<ArrayView2<A>>, weights: ArrayView1<A>) -> Result<Array2<A>> {
+        DTW::dba(
+            points,
+            None,
+            None,
+            30,
+            A::from_f32(0.00005).unwrap(),
+            Some(weights.to_owned()),
+            1,
+        )
+    }
 }
 
 #[cfg(test)]
@@ -274,6 +307,7 @@ mod tests {
     use ndarray::{arr2, Array2};
 
     use crate::distance_measure::dtw::DTW;
+    use crate::error::MeanShiftError;
     use crate::DistanceMeasure;
 
     #[test]
@@ -303,6 +337,14 @@ mod tests {
         assert!((distance - 0.6698380712497375).abs() < 1e-7)
     }
 
+    #[test]
+    fn test_dba_empty() {
+        assert_eq!(
+            <DTW as DistanceMeasure<f64>>::mean(vec![]),
+            Err(MeanShiftError::EmptyInput)
+        );
+    }
+
     #[test]
     fn test_dba_same_lengths() {
         let a = arr2(&[[
@@ -362,6 +404,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_dba_resized_barycenter() {
+        let a = arr2(&[[0.0, 1.0, 2.0, 3.0, 4.0]]);
+        let b = arr2(&[[0.0, 1.0, 2.0, 3.0, 4.0]]);
+
+        let center = DTW::dba(vec![a.t(), b.t()], Some(3), None, 30, 1e-5, None, 1).unwrap();
+
+        assert_eq!(center.shape(), &[3, 1]);
+    }
+
     #[test]
     fn test_cost_matrix() {
         let a = arr2(&[[
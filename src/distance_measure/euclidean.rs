@@ -1,21 +1,36 @@
 use std::ops::Div;
 
-use crate::distance_measure::DistanceMeasure;
+use crate::distance_measure::{lane_sum, DistanceMeasure};
+use crate::error::{MeanShiftError, Result};
+use crate::neighbors::IndexBackend;
+use crate::params::ParameterError;
 use crate::utils::LibData;
-use anyhow::{Error, Result};
 use kdtree::distance::squared_euclidean;
-use ndarray::{Array2, ArrayView2};
+use ndarray::{Array2, ArrayView1, ArrayView2};
 
 #[derive(Copy, Clone, Default)]
 pub struct Euclidean;
 
 impl<A: LibData> DistanceMeasure<A> for Euclidean {
     const NAME: &'static str = "euclidean";
+    const INDEX_BACKENDS: &'static [IndexBackend] = &[
+        IndexBackend::KdTree,
+        IndexBackend::Grid,
+        IndexBackend::VpTree,
+        IndexBackend::Blocked,
+        IndexBackend::BruteForce,
+    ];
 
     fn distance_slice(point_a: &[A], point_b: &[A]) -> A {
         squared_euclidean(point_a, point_b).sqrt()
     }
 
+    fn distances_to_rows(query: &[A], rows: &[A], distances: &mut [A]) {
+        for (row, distance) in rows.chunks_exact(query.len()).zip(distances.iter_mut()) {
+            *distance = lane_sum(query, row, |a, b| (a - b) * (a - b)).sqrt();
+        }
+    }
+
     fn distance(series_a: ArrayView2<A>, series_b: ArrayView2<A>) -> A {
         series_a
             .iter()
@@ -26,10 +41,7 @@ impl<A: LibData> DistanceMeasure<A> for Euclidean {
     }
 
     fn mean(points: Vec<ArrayView2<A>>) -> Result<Array2<A>> {
-        let el_shape = points
-            .get(0)
-            .ok_or_else(|| Error::msg("Empty points list"))?
-            .shape();
+        let el_shape = points.first().ok_or(MeanShiftError::EmptyInput)?.shape();
         let el_len = el_shape[0];
         let el_d = el_shape[1];
         let el_n = points.len();
@@ -39,13 +51,27 @@ impl<A: LibData> DistanceMeasure<A> for Euclidean {
             .fold(sum_vec, |a, b| a + b)
             .div(A::from_usize(el_n).unwrap()))
     }
+
+    fn weighted_mean(points: Vec<ArrayView2<A>>, weights: ArrayView1<A>) -> Result<Array2<A>> {
+        let el_shape = points.first().ok_or(MeanShiftError::EmptyInput)?.shape();
+        let weight_sum = weights.sum();
+        if weight_sum <= A::zero() {
+            return Err(ParameterError::NonPositiveWeightSum.into());
+        }
+        let sum_vec = Array2::zeros([el_shape[0], el_shape[1]]);
+        Ok(points
+            .into_iter()
+            .zip(weights.iter())
+            .fold(sum_vec, |a, (b, w)| a + &b * *w)
+            .div(weight_sum))
+    }
 }
 
 #[cfg(test)]
 mod test {
     use crate::distance_measure::Euclidean;
     use crate::DistanceMeasure;
-    use ndarray::{arr2, Axis};
+    use ndarray::{arr1, arr2, Axis};
 
     #[test]
     fn test_distance_is_same() {
@@ -60,4 +86,15 @@ mod test {
             Euclidean::distance(a.t(), b.t())
         )
     }
+
+    #[test]
+    fn test_weighted_mean() {
+        let a = arr2(&[[0.0, 1.0, 2.0]]);
+        let b = arr2(&[[3.0, 4.0, 5.0]]);
+        let weights = arr1(&[1.0, 2.0]);
+
+        let mean = Euclidean::weighted_mean(vec![a.view(), b.view()], weights.view()).unwrap();
+
+        assert_eq!(mean, arr2(&[[2.0, 3.0, 4.0]]))
+    }
 }
@@ -1,13 +1,21 @@
-use crate::distance_measure::{DistanceMeasure, Euclidean};
+use crate::distance_measure::{lane_sum, DistanceMeasure, Euclidean};
+use crate::error::Result;
+use crate::neighbors::IndexBackend;
 use crate::utils::LibData;
-use anyhow::Result;
-use ndarray::{Array2, ArrayView2};
+use ndarray::{Array2, ArrayView1, ArrayView2};
 
 #[derive(Copy, Clone, Default)]
 pub struct Manhattan;
 
 impl<A: LibData> DistanceMeasure<A> for Manhattan {
     const NAME: &'static str = "manhattan";
+    const INDEX_BACKENDS: &'static [IndexBackend] = &[
+        IndexBackend::KdTree,
+        IndexBackend::Grid,
+        IndexBackend::VpTree,
+        IndexBackend::Blocked,
+        IndexBackend::BruteForce,
+    ];
 
     fn distance_slice(point_a: &[A], point_b: &[A]) -> A {
         point_a
@@ -17,6 +25,12 @@ impl<A: LibData> DistanceMeasure<A> for Manhattan {
             .sum()
     }
 
+    fn distances_to_rows(query: &[A], rows: &[A], distances: &mut [A]) {
+        for (row, distance) in rows.chunks_exact(query.len()).zip(distances.iter_mut()) {
+            *distance = lane_sum(query, row, |a, b| (a - b).abs());
+        }
+    }
+
     fn distance(series_a: ArrayView2<A>, series_b: ArrayView2<A>) -> A {
         series_a
             .iter()
@@ -28,6 +42,10 @@ impl<A: LibData> DistanceMeasure<A> for Manhattan {
     fn mean(points: Vec<ArrayView2<A>>) -> Result<Array2<A>> {
         Euclidean::mean(points)
     }
+
+    fn weighted_mean(points: Vec<ArrayView2<A>>, weights: ArrayView1<A>) -> Result<Array2<A>> {
+        Euclidean::weighted_mean(points, weights)
+    }
 }
 
 #[cfg(test)]
@@ -1,28 +1,75 @@
+use crate::error::Result;
+use crate::neighbors::IndexBackend;
 use crate::utils::LibData;
-use anyhow::Result;
 
+pub mod chebyshev;
 pub mod dtw;
 pub mod euclidean;
 pub mod manhattan;
 
+pub use chebyshev::Chebyshev;
 pub use dtw::DTW;
 pub use euclidean::Euclidean;
 pub use manhattan::Manhattan;
 use ndarray::{Array1, Array2, ArrayView1, ArrayView2, Axis};
 
+/// Number of independent accumulators of `lane_sum`.
+const LANES: usize = 8;
+
+/// Sums `f` over the pairs of elements of `a` and `b` in `LANES` independent partial sums, which
+/// lets the compiler turn the loop into SIMD instructions.
+pub(crate) fn lane_sum<A: LibData>(a: &[A], b: &[A], f: impl Fn(A, A) -> A) -> A {
+    let mut sums = [A::zero(); LANES];
+    let a_chunks = a.chunks_exact(LANES);
+    let b_chunks = b.chunks_exact(LANES);
+    let rest: A = a_chunks
+        .remainder()
+        .iter()
+        .zip(b_chunks.remainder())
+        .map(|(x, y)| f(*x, *y))
+        .sum();
+    for (a_chunk, b_chunk) in a_chunks.zip(b_chunks) {
+        for lane in 0..LANES {
+            sums[lane] = sums[lane] + f(a_chunk[lane], b_chunk[lane]);
+        }
+    }
+    sums.iter().fold(rest, |sum, lane| sum + *lane)
+}
+
 pub trait DistanceMeasure<A: LibData>
 where
-    Self: Default + Copy + Clone + Sync,
+    Self: Default + Copy + Clone + Send + Sync + 'static,
 {
     const NAME: &'static str;
+    /// Whether rows may be right-padded with NaN to store series of different lengths.
+    const ALLOWS_NAN_PADDING: bool = false;
+    /// Neighbor index backends that find all neighbors under this measure, the preferred one
+    /// first.
+    const INDEX_BACKENDS: &'static [IndexBackend] =
+        &[IndexBackend::BruteForce, IndexBackend::Blocked];
 
     fn distance_slice(series_a: &[A], series_b: &[A]) -> A;
+
+    /// Writes the distance of `query` to every row of the row-major `rows` into `distances`.
+    /// Measures override this with a kernel the compiler can vectorize.
+    fn distances_to_rows(query: &[A], rows: &[A], distances: &mut [A]) {
+        for (row, distance) in rows.chunks_exact(query.len()).zip(distances.iter_mut()) {
+            *distance = Self::distance_slice(query, row);
+        }
+    }
     fn distance(series_a: ArrayView2<A>, series_b: ArrayView2<A>) -> A;
     fn mean(points: Vec<ArrayView2<A>>) -> Result<Array2<A>>;
+    fn weighted_mean(points: Vec<ArrayView2<A>>, weights: ArrayView1<A>) -> Result<Array2<A>>;
 
     fn mean_1d(points: Vec<ArrayView1<A>>) -> Result<Array1<A>> {
         let points: Vec<ArrayView2<A>> =
             points.into_iter().map(|x| x.insert_axis(Axis(0))).collect();
         Ok(Self::mean(points)?.index_axis_move(Axis(0), 0))
     }
+
+    fn weighted_mean_1d(points: Vec<ArrayView1<A>>, weights: ArrayView1<A>) -> Result<Array1<A>> {
+        let points: Vec<ArrayView2<A>> =
+            points.into_iter().map(|x| x.insert_axis(Axis(0))).collect();
+        Ok(Self::weighted_mean(points, weights)?.index_axis_move(Axis(0), 0))
+    }
 }
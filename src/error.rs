@@ -0,0 +1,109 @@
+use crate::params::ParameterError;
+use ndarray::ShapeError;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+pub type Result<T> = std::result::Result<T, MeanShiftError>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeanShiftError {
+    /// The data, the seeds or the points to average have no rows or no columns.
+    EmptyInput,
+    /// The data holds a NaN or an infinite value at the given position.
+    NonFiniteValue {
+        row: usize,
+        column: usize,
+    },
+    /// An array has `actual` columns where `expected` were required.
+    DimensionMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// There are `actual` weights for `expected` rows.
+    WeightCountMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// A precomputed distance matrix holds a negative distance at the given position.
+    NegativeDistance {
+        row: usize,
+        column: usize,
+    },
+    /// `predict` was called before `cluster`.
+    NotFitted,
+    InvalidParameter(ParameterError),
+    /// No seed ended up with at least `min_bin_freq` neighbors.
+    NoModeFound {
+        min_bin_freq: usize,
+    },
+    /// The neighbor index rejected a point.
+    Index(String),
+    Shape(String),
+    ThreadPool(String),
+}
+
+impl Display for MeanShiftError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyInput => write!(f, "the input must have at least one row and column"),
+            Self::NonFiniteValue { row, column } => {
+                write!(f, "non-finite value in row {} column {}", row, column)
+            }
+            Self::DimensionMismatch { expected, actual } => {
+                write!(f, "expected {} columns, got {}", expected, actual)
+            }
+            Self::WeightCountMismatch { expected, actual } => {
+                write!(f, "expected {} weights, got {}", expected, actual)
+            }
+            Self::NegativeDistance { row, column } => {
+                write!(f, "negative distance in row {} column {}", row, column)
+            }
+            Self::NotFitted => write!(
+                f,
+                "the model is not fitted yet, call `cluster` before `predict`"
+            ),
+            Self::InvalidParameter(error) => write!(f, "invalid parameter: {}", error),
+            Self::NoModeFound { min_bin_freq } => write!(
+                f,
+                "no seed has at least min_bin_freq={} neighbors within the bandwidth",
+                min_bin_freq
+            ),
+            Self::Index(message) => write!(f, "neighbor index error: {}", message),
+            Self::Shape(message) => write!(f, "shape error: {}", message),
+            Self::ThreadPool(message) => write!(f, "could not build thread pool: {}", message),
+        }
+    }
+}
+
+impl Error for MeanShiftError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::InvalidParameter(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParameterError> for MeanShiftError {
+    fn from(error: ParameterError) -> Self {
+        Self::InvalidParameter(error)
+    }
+}
+
+impl From<kdtree::ErrorKind> for MeanShiftError {
+    fn from(error: kdtree::ErrorKind) -> Self {
+        Self::Index(format!("{:?}", error))
+    }
+}
+
+impl From<ShapeError> for MeanShiftError {
+    fn from(error: ShapeError) -> Self {
+        Self::Shape(error.to_string())
+    }
+}
+
+impl From<rayon::ThreadPoolBuildError> for MeanShiftError {
+    fn from(error: rayon::ThreadPoolBuildError) -> Self {
+        Self::ThreadPool(error.to_string())
+    }
+}
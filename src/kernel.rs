@@ -0,0 +1,137 @@
+use crate::utils::LibData;
+
+/// Kernel profile that weights the neighbors of a point during the mean update.
+///
+/// `weight` receives the distance between the current mean and a neighbor divided by the
+/// bandwidth. Only neighbors within `truncation` bandwidths are passed to the kernel.
+pub trait Kernel
+where
+    Self: Default + Copy + Clone + Send + Sync,
+{
+    const NAME: &'static str;
+
+    fn weight<A: LibData>(&self, u: A) -> A;
+
+    fn truncation<A: LibData>(&self) -> A {
+        A::one()
+    }
+}
+
+/// Every neighbor within the bandwidth gets the same weight.
+#[derive(Copy, Clone, Default)]
+pub struct Flat;
+
+impl Kernel for Flat {
+    const NAME: &'static str = "flat";
+
+    fn weight<A: LibData>(&self, u: A) -> A {
+        if u <= A::one() {
+            A::one()
+        } else {
+            A::zero()
+        }
+    }
+}
+
+/// Gaussian profile `exp(-u² / 2)`, cut off after `truncation` bandwidths.
+#[derive(Copy, Clone)]
+pub struct Gaussian {
+    pub truncation: f64,
+}
+
+impl Default for Gaussian {
+    fn default() -> Self {
+        Self { truncation: 3.0 }
+    }
+}
+
+impl Kernel for Gaussian {
+    const NAME: &'static str = "gaussian";
+
+    fn weight<A: LibData>(&self, u: A) -> A {
+        if u <= self.truncation::<A>() {
+            (-u.powi(2) / A::from_f32(2.0).unwrap()).exp()
+        } else {
+            A::zero()
+        }
+    }
+
+    fn truncation<A: LibData>(&self) -> A {
+        A::from_f64(self.truncation).unwrap()
+    }
+}
+
+fn compact_profile<A: LibData>(u: A, power: i32) -> A {
+    if u < A::one() {
+        (A::one() - u.powi(2)).powi(power)
+    } else {
+        A::zero()
+    }
+}
+
+/// Epanechnikov profile `1 - u²`.
+#[derive(Copy, Clone, Default)]
+pub struct Epanechnikov;
+
+impl Kernel for Epanechnikov {
+    const NAME: &'static str = "epanechnikov";
+
+    fn weight<A: LibData>(&self, u: A) -> A {
+        compact_profile(u, 1)
+    }
+}
+
+/// Biweight (quartic) profile `(1 - u²)²`.
+#[derive(Copy, Clone, Default)]
+pub struct Biweight;
+
+impl Kernel for Biweight {
+    const NAME: &'static str = "biweight";
+
+    fn weight<A: LibData>(&self, u: A) -> A {
+        compact_profile(u, 2)
+    }
+}
+
+/// Triweight profile `(1 - u²)³`.
+#[derive(Copy, Clone, Default)]
+pub struct Triweight;
+
+impl Kernel for Triweight {
+    const NAME: &'static str = "triweight";
+
+    fn weight<A: LibData>(&self, u: A) -> A {
+        compact_profile(u, 3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::close_l1;
+
+    #[test]
+    fn test_flat() {
+        assert_eq!(Flat.weight(0.0_f64), 1.0);
+        assert_eq!(Flat.weight(1.0_f64), 1.0);
+        assert_eq!(Flat.weight(1.5_f64), 0.0);
+    }
+
+    #[test]
+    fn test_gaussian_truncation() {
+        let kernel = Gaussian::default();
+        assert_eq!(kernel.weight(0.0_f64), 1.0);
+        close_l1(kernel.weight(1.0_f64), 0.6065306597, 1e-9);
+        assert_eq!(kernel.weight(3.5_f64), 0.0);
+        assert_eq!(kernel.truncation::<f64>(), 3.0);
+    }
+
+    #[test]
+    fn test_compact_profiles() {
+        close_l1(Epanechnikov.weight(0.5_f64), 0.75, 1e-12);
+        close_l1(Biweight.weight(0.5_f64), 0.5625, 1e-12);
+        close_l1(Triweight.weight(0.5_f64), 0.421875, 1e-12);
+        assert_eq!(Epanechnikov.weight(1.0_f64), 0.0);
+        assert_eq!(Biweight.truncation::<f64>(), 1.0);
+    }
+}
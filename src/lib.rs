@@ -1,12 +1,32 @@
 extern crate core;
 
+pub mod bandwidth;
+pub mod deduplication;
 pub mod distance_measure;
+pub mod error;
+pub mod kernel;
+pub mod medoid_shift;
+pub mod neighbors;
 mod parallel;
+pub mod params;
 #[cfg(feature = "python")]
 mod python_binding;
+pub mod quick_shift;
+pub mod result;
+pub mod seeding;
 #[cfg(test)]
 mod test_utils;
 mod utils;
 
+pub use bandwidth::{estimate_bandwidth, Anisotropy, BandwidthEstimator, DimensionEstimator};
+pub use deduplication::Deduplication;
 pub use distance_measure::DistanceMeasure;
-pub use parallel::MeanShift;
+pub use error::MeanShiftError;
+pub use kernel::Kernel;
+pub use medoid_shift::MedoidShift;
+pub use neighbors::{HnswParams, IndexBackend, NeighborIndex};
+pub use parallel::{MeanShift, NOISE_LABEL};
+pub use params::{MeanShiftParams, ParameterError, Variant};
+pub use quick_shift::QuickShift;
+pub use result::{ClusteringResult, LabelAgreement, MedoidShiftResult, QuickShiftResult};
+pub use seeding::Seeding;
@@ -0,0 +1,292 @@
+use crate::bandwidth::{knn_quantile_of_distances, DEFAULT_QUANTILE};
+use crate::distance_measure::DistanceMeasure;
+use crate::error::{MeanShiftError, Result};
+use crate::kernel::{Flat, Kernel};
+use crate::params::validate_bandwidth;
+use crate::result::MedoidShiftResult;
+use crate::utils::{forest_roots, validate_data, LibData};
+use ndarray::{Array2, ArrayView2, Axis};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+/// Medoid shift, a mode-seeking clustering that only needs pairwise distances.
+///
+/// Every point moves to its kernel-weighted medoid: the neighbor that minimizes the
+/// kernel-weighted sum of squared distances to the neighbors of the point. Following these moves
+/// ends at a medoid that stays where it is. The medoids are shifted again, weighted with the
+/// number of points that reached them, until none of them moves, and all points that reach a
+/// medoid form a cluster. As no means are computed, elastic distances such as `DTW` need no
+/// barycenter averaging, and a precomputed distance matrix can be clustered directly.
+#[derive(Default)]
+pub struct MedoidShift<A: LibData, D: DistanceMeasure<A>, K: Kernel = Flat> {
+    pub bandwidth: Option<A>,
+    pub kernel: K,
+    distance_measure: PhantomData<D>,
+}
+
+impl<A: LibData, D: DistanceMeasure<A>> MedoidShift<A, D> {
+    pub fn new(_distance_measure: D, bandwidth: Option<A>) -> Self {
+        Self {
+            bandwidth,
+            kernel: Flat,
+            distance_measure: PhantomData,
+        }
+    }
+}
+
+impl<A: LibData, D: DistanceMeasure<A>, K: Kernel> MedoidShift<A, D, K> {
+    pub fn with_kernel<K2: Kernel>(self, kernel: K2) -> MedoidShift<A, D, K2> {
+        MedoidShift {
+            bandwidth: self.bandwidth,
+            kernel,
+            distance_measure: PhantomData,
+        }
+    }
+
+    /// Clusters the rows of `dataset` under the distance measure `D`.
+    pub fn cluster(&mut self, dataset: ArrayView2<A>) -> Result<MedoidShiftResult<A>> {
+        validate_data::<A, D>(dataset)?;
+        let distances = pairwise_distances::<A, D>(dataset);
+        self.cluster_distances(distances.view())
+    }
+
+    /// Clusters the points of a square matrix of precomputed pairwise distances. `D` is not
+    /// used.
+    pub fn cluster_precomputed(
+        &mut self,
+        distances: ArrayView2<A>,
+    ) -> Result<MedoidShiftResult<A>> {
+        validate_distances(distances)?;
+        self.cluster_distances(distances)
+    }
+
+    fn cluster_distances(&mut self, distances: ArrayView2<A>) -> Result<MedoidShiftResult<A>> {
+        let bandwidth = match self.bandwidth {
+            Some(bandwidth) => {
+                validate_bandwidth(bandwidth)?;
+                bandwidth
+            }
+            None => knn_quantile_of_distances(distances, DEFAULT_QUANTILE)?,
+        };
+        self.bandwidth = Some(bandwidth);
+
+        let n = distances.shape()[0];
+        let mut medoids: Vec<usize> = (0..n).collect();
+        let mut weights: Vec<A> = vec![A::one(); n];
+        let mut medoid_of: Vec<usize> = (0..n).collect();
+        let mut iterations = 0;
+        loop {
+            let parents: Vec<usize> = (0..medoids.len())
+                .into_par_iter()
+                .map(|m| self.medoid(distances, &medoids, &weights, m, bandwidth))
+                .collect();
+            iterations += 1;
+            let roots = forest_roots(&parents);
+            if roots.iter().enumerate().all(|(m, root)| m == *root) {
+                break;
+            }
+
+            let position: HashMap<usize, usize> = medoids
+                .iter()
+                .enumerate()
+                .map(|(m, point)| (*point, m))
+                .collect();
+            for medoid in medoid_of.iter_mut() {
+                *medoid = medoids[roots[position[medoid]]];
+            }
+            let mut merged: HashMap<usize, A> = HashMap::new();
+            for (m, root) in roots.iter().enumerate() {
+                let weight = merged.entry(medoids[*root]).or_insert_with(A::zero);
+                *weight = *weight + weights[m];
+            }
+            medoids = merged.keys().copied().collect();
+            medoids.sort_unstable();
+            weights = medoids.iter().map(|medoid| merged[medoid]).collect();
+        }
+
+        let mut sizes: HashMap<usize, usize> = HashMap::new();
+        for medoid in medoid_of.iter() {
+            *sizes.entry(*medoid).or_insert(0) += 1;
+        }
+        // largest clusters first
+        medoids.sort_by(|a, b| sizes[b].cmp(&sizes[a]).then(a.cmp(b)));
+        let label_of: HashMap<usize, i32> = medoids
+            .iter()
+            .enumerate()
+            .map(|(label, medoid)| (*medoid, label as i32))
+            .collect();
+
+        Ok(MedoidShiftResult {
+            labels: medoid_of.iter().map(|medoid| label_of[medoid]).collect(),
+            support: medoids.iter().map(|medoid| sizes[medoid]).collect(),
+            medoids,
+            bandwidth,
+            iterations,
+        })
+    }
+
+    /// Weighted medoid of the neighbors of `medoids[m]` among the `medoids`, as an index into
+    /// `medoids`. Ties go to the neighbor with the smallest index, so duplicates merge.
+    fn medoid(
+        &self,
+        distances: ArrayView2<A>,
+        medoids: &[usize],
+        weights: &[A],
+        m: usize,
+        bandwidth: A,
+    ) -> usize {
+        let radius = bandwidth * self.kernel.truncation();
+        let neighbors: Vec<(usize, A)> = medoids
+            .iter()
+            .zip(weights.iter())
+            .enumerate()
+            .filter(|(_, (point, _))| distances[[medoids[m], **point]] <= radius)
+            .map(|(n, (point, weight))| {
+                let kernel_weight = self
+                    .kernel
+                    .weight(distances[[medoids[m], *point]] / bandwidth);
+                (n, kernel_weight * *weight)
+            })
+            .filter(|(_, weight)| *weight > A::zero())
+            .collect();
+        let cost = |candidate: usize| -> A {
+            neighbors
+                .iter()
+                .map(|(n, weight)| *weight * distances[[medoids[candidate], medoids[*n]]].powi(2))
+                .sum()
+        };
+
+        let (mut medoid, mut min_cost) = (m, cost(m));
+        for (n, _) in neighbors.iter() {
+            let cost = cost(*n);
+            if cost < min_cost || (cost == min_cost && *n < medoid) {
+                medoid = *n;
+                min_cost = cost;
+            }
+        }
+        medoid
+    }
+}
+
+/// Symmetric matrix of the distances between all rows of `data` under `D`.
+fn pairwise_distances<A: LibData, D: DistanceMeasure<A>>(data: ArrayView2<A>) -> Array2<A> {
+    let data = data.as_standard_layout();
+    let rows: Vec<&[A]> = data
+        .axis_iter(Axis(0))
+        .map(|row| row.to_slice().unwrap())
+        .collect();
+    let n = rows.len();
+    let upper: Vec<Vec<A>> = (0..n)
+        .into_par_iter()
+        .map(|i| {
+            rows[i + 1..]
+                .iter()
+                .map(|other| D::distance_slice(rows[i], other))
+                .collect()
+        })
+        .collect();
+
+    let mut distances = Array2::zeros([n, n]);
+    for (i, row) in upper.into_iter().enumerate() {
+        for (offset, distance) in row.into_iter().enumerate() {
+            distances[[i, i + 1 + offset]] = distance;
+            distances[[i + 1 + offset, i]] = distance;
+        }
+    }
+    distances
+}
+
+fn validate_distances<A: LibData>(distances: ArrayView2<A>) -> Result<()> {
+    if distances.shape()[0] == 0 {
+        return Err(MeanShiftError::EmptyInput);
+    }
+    if distances.shape()[0] != distances.shape()[1] {
+        return Err(MeanShiftError::DimensionMismatch {
+            expected: distances.shape()[0],
+            actual: distances.shape()[1],
+        });
+    }
+    for ((row, column), distance) in distances.indexed_iter() {
+        if !distance.is_finite() {
+            return Err(MeanShiftError::NonFiniteValue { row, column });
+        }
+        if *distance < A::zero() {
+            return Err(MeanShiftError::NegativeDistance { row, column });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distance_measure::{Euclidean, DTW};
+    use crate::kernel::Gaussian;
+    use crate::test_utils::circle_blobs;
+    use ndarray::arr2;
+
+    #[test]
+    fn test_medoid_shift() {
+        // circles around their centers, which are their medoids
+        let centers = [[0.0, 0.0], [5.0, 0.0], [0.0, 5.0]];
+        let mut dataset = circle_blobs(&centers, 12, 1.0);
+        dataset
+            .append(Axis(0), circle_blobs(&centers, 1, 0.0).view())
+            .unwrap();
+        let mut medoid_shift = MedoidShift::new(Euclidean, Some(2.0));
+        let result = medoid_shift.cluster(dataset.view()).unwrap();
+
+        assert_eq!(3, result.n_clusters());
+        assert_eq!(vec![13, 13, 13], result.support);
+        assert_eq!(vec![36, 37, 38], result.medoids);
+        for (i, label) in result.labels.iter().take(36).enumerate() {
+            assert_eq!(result.labels[36 + i / 12], *label);
+        }
+
+        let distances = pairwise_distances::<_, Euclidean>(dataset.view());
+        let mut precomputed =
+            MedoidShift::new(Euclidean, Some(1.0)).with_kernel(Gaussian::default());
+        let precomputed = precomputed.cluster_precomputed(distances.view()).unwrap();
+        assert_eq!(result.labels, precomputed.labels);
+    }
+
+    #[test]
+    fn test_medoid_shift_dtw() {
+        let dataset = arr2(&[
+            [0.0, 1.0, 2.0, 3.0, 4.0],
+            [0.0, 0.0, 1.0, 2.0, 3.0],
+            [0.0, 1.0, 2.0, 3.0, 3.0],
+            [9.0, 7.0, 5.0, 3.0, 1.0],
+            [9.0, 9.0, 7.0, 5.0, 3.0],
+            [9.0, 7.0, 7.0, 5.0, 3.0],
+        ]);
+        let mut medoid_shift = MedoidShift::new(DTW, Some(4.0));
+        let result = medoid_shift.cluster(dataset.view()).unwrap();
+
+        assert_eq!(2, result.n_clusters());
+        assert_eq!(result.labels[0], result.labels[2]);
+        assert_eq!(result.labels[3], result.labels[5]);
+        assert_ne!(result.labels[0], result.labels[3]);
+    }
+
+    #[test]
+    fn test_invalid_distance_matrix() {
+        let mut medoid_shift = MedoidShift::new(Euclidean, None);
+        assert_eq!(
+            medoid_shift
+                .cluster_precomputed(Array2::<f64>::zeros([2, 3]).view())
+                .unwrap_err(),
+            MeanShiftError::DimensionMismatch {
+                expected: 2,
+                actual: 3
+            }
+        );
+        assert_eq!(
+            medoid_shift
+                .cluster_precomputed(arr2(&[[0.0, -1.0], [-1.0, 0.0]]).view())
+                .unwrap_err(),
+            MeanShiftError::NegativeDistance { row: 0, column: 1 }
+        );
+    }
+}
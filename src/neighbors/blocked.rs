@@ -0,0 +1,78 @@
+use crate::distance_measure::DistanceMeasure;
+use crate::error::Result;
+use crate::neighbors::{compare_neighbors, IndexBackend, NeighborIndex, Rows};
+use crate::utils::LibData;
+use rayon::prelude::*;
+use std::marker::PhantomData;
+use std::mem::size_of;
+
+/// Bytes of the rows in one tile, small enough for the tile to stay in the L2 cache.
+const TILE_BYTES: usize = 1 << 16;
+
+/// Compares the query with every row, tile by tile in parallel, using
+/// `DistanceMeasure::distances_to_rows` for the distances of a tile.
+///
+/// Beyond a few dozen dimensions, trees prune too little to beat this scan.
+pub struct Blocked<'a, A: LibData, D: DistanceMeasure<A>> {
+    data: Rows<'a, A>,
+    tile_rows: usize,
+    distance_measure: PhantomData<D>,
+}
+
+impl<'a, A: LibData, D: DistanceMeasure<A>> Blocked<'a, A, D> {
+    pub fn new(data: impl Into<Rows<'a, A>>) -> Self {
+        let data = data.into();
+        let row_bytes = data.n_columns().max(1) * size_of::<A>();
+        Self {
+            data,
+            tile_rows: (TILE_BYTES / row_bytes).max(1),
+            distance_measure: PhantomData,
+        }
+    }
+
+    /// Distances of `query` to all rows of every tile that pass `keep`.
+    fn scan(&self, query: &[A], keep: impl Fn(A) -> bool + Sync) -> Vec<(A, usize)> {
+        let n_columns = query.len();
+        self.data
+            .as_slice()
+            .par_chunks(self.tile_rows * n_columns)
+            .enumerate()
+            .flat_map_iter(|(tile, rows)| {
+                let mut distances = vec![A::zero(); rows.len() / n_columns];
+                D::distances_to_rows(query, rows, &mut distances);
+                let offset = tile * self.tile_rows;
+                distances
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(_, distance)| keep(*distance))
+                    .map(move |(i, distance)| (distance, offset + i))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+impl<A: LibData, D: DistanceMeasure<A>> NeighborIndex<A> for Blocked<'_, A, D> {
+    fn backend(&self) -> IndexBackend {
+        IndexBackend::Blocked
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn within(&self, query: &[A], radius: A) -> Result<Vec<(A, usize)>> {
+        Ok(self.scan(query, |distance| distance <= radius))
+    }
+
+    fn nearest(&self, query: &[A], k: usize) -> Result<Vec<(A, usize)>> {
+        let mut neighbors = self.scan(query, |_| true);
+        let k = k.min(neighbors.len());
+        if k > 0 && k < neighbors.len() {
+            neighbors.select_nth_unstable_by(k - 1, compare_neighbors);
+        }
+        neighbors.truncate(k);
+        neighbors.sort_by(compare_neighbors);
+        Ok(neighbors)
+    }
+}
@@ -0,0 +1,57 @@
+use crate::distance_measure::DistanceMeasure;
+use crate::error::Result;
+use crate::neighbors::{compare_neighbors, IndexBackend, NeighborIndex, Rows};
+use crate::utils::LibData;
+use std::marker::PhantomData;
+
+/// Compares the query with every row.
+pub struct BruteForce<'a, A: LibData, D: DistanceMeasure<A>> {
+    data: Rows<'a, A>,
+    distance_measure: PhantomData<D>,
+}
+
+impl<'a, A: LibData, D: DistanceMeasure<A>> BruteForce<'a, A, D> {
+    pub fn new(data: impl Into<Rows<'a, A>>) -> Self {
+        Self {
+            data: data.into(),
+            distance_measure: PhantomData,
+        }
+    }
+
+    fn distances<'b>(&'b self, query: &'b [A]) -> impl Iterator<Item = (A, usize)> + 'b {
+        let n_columns = self.data.n_columns();
+        let rows = self.data.as_slice();
+        (0..self.data.len()).map(move |i| {
+            let row = &rows[i * n_columns..(i + 1) * n_columns];
+            (D::distance_slice(query, row), i)
+        })
+    }
+}
+
+impl<A: LibData, D: DistanceMeasure<A>> NeighborIndex<A> for BruteForce<'_, A, D> {
+    fn backend(&self) -> IndexBackend {
+        IndexBackend::BruteForce
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn within(&self, query: &[A], radius: A) -> Result<Vec<(A, usize)>> {
+        Ok(self
+            .distances(query)
+            .filter(|(d, _)| *d <= radius)
+            .collect())
+    }
+
+    fn nearest(&self, query: &[A], k: usize) -> Result<Vec<(A, usize)>> {
+        let mut neighbors: Vec<(A, usize)> = self.distances(query).collect();
+        let k = k.min(neighbors.len());
+        if k > 0 && k < neighbors.len() {
+            neighbors.select_nth_unstable_by(k - 1, compare_neighbors);
+        }
+        neighbors.truncate(k);
+        neighbors.sort_by(compare_neighbors);
+        Ok(neighbors)
+    }
+}
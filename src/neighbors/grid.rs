@@ -0,0 +1,303 @@
+use crate::distance_measure::DistanceMeasure;
+use crate::error::Result;
+use crate::neighbors::{compare_neighbors, IndexBackend, NeighborIndex, Rows};
+use crate::utils::LibData;
+use ndarray::{ArrayView2, Axis};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+/// Uniform grid that hashes every point into the cell `floor(x / cell_size)`.
+///
+/// A query only compares the points in the cells that overlap the box of the radius around it,
+/// which finds all neighbors if no coordinate differs by more than the distance, as for
+/// Euclidean, Manhattan and Chebyshev distances. The number of these cells grows exponentially
+/// with the dimension, so the grid only pays off for a few dimensions and a cell size close to
+/// the radius of the queries.
+pub struct Grid<'a, A: LibData, D: DistanceMeasure<A>> {
+    data: Rows<'a, A>,
+    cell_size: A,
+    cells: HashMap<Vec<i64>, Vec<usize>>,
+    /// Smallest and largest key of any point along every dimension.
+    lower: Vec<i64>,
+    upper: Vec<i64>,
+    distance_measure: PhantomData<D>,
+}
+
+impl<'a, A: LibData, D: DistanceMeasure<A>> Grid<'a, A, D> {
+    /// Without a `cell_size`, the cells are chosen such that the bounding box of the data
+    /// spans about as many cells as there are points.
+    pub fn new(data: impl Into<Rows<'a, A>>, cell_size: Option<A>) -> Self {
+        let data = data.into();
+        let cell_size = cell_size
+            .filter(|c| c.is_finite() && *c > A::zero())
+            .unwrap_or_else(|| fitting_cell_size(data.view()));
+        let n_columns = data.n_columns();
+        let mut cells: HashMap<Vec<i64>, Vec<usize>> = HashMap::new();
+        let mut lower = vec![i64::MAX; n_columns];
+        let mut upper = vec![i64::MIN; n_columns];
+        for (i, row) in data.view().axis_iter(Axis(0)).enumerate() {
+            let key: Vec<i64> = row.iter().map(|x| cell_key(*x, cell_size)).collect();
+            for ((l, u), k) in lower.iter_mut().zip(upper.iter_mut()).zip(key.iter()) {
+                *l = (*l).min(*k);
+                *u = (*u).max(*k);
+            }
+            cells.entry(key).or_default().push(i);
+        }
+        Self {
+            data,
+            cell_size,
+            cells,
+            lower,
+            upper,
+            distance_measure: PhantomData,
+        }
+    }
+
+    /// Keys of the cells around `query` that contain points within `reach` along every
+    /// dimension, or `None` if there are none.
+    fn key_box(&self, query: &[A], reach: A) -> Option<(Vec<i64>, Vec<i64>)> {
+        let from: Vec<i64> = query
+            .iter()
+            .zip(self.lower.iter())
+            .map(|(q, l)| cell_key(*q - reach, self.cell_size).max(*l))
+            .collect();
+        let to: Vec<i64> = query
+            .iter()
+            .zip(self.upper.iter())
+            .map(|(q, u)| cell_key(*q + reach, self.cell_size).min(*u))
+            .collect();
+        if self.cells.is_empty() || from.iter().zip(to.iter()).any(|(f, t)| f > t) {
+            return None;
+        }
+        Some((from, to))
+    }
+
+    /// Calls `visit` with the points of every cell in the box from `from` to `to` whose key
+    /// passes `keep`, looking the cells up one by one or going through all occupied cells,
+    /// whichever is fewer.
+    fn for_each_cell(
+        &self,
+        from: &[i64],
+        to: &[i64],
+        keep: impl Fn(&[i64]) -> bool,
+        mut visit: impl FnMut(&[usize]),
+    ) {
+        if box_size(from, to) > self.cells.len() as f64 {
+            let in_box = |key: &[i64]| {
+                key.iter()
+                    .zip(from.iter().zip(to.iter()))
+                    .all(|(k, (f, t))| f <= k && k <= t)
+            };
+            for (key, points) in self.cells.iter() {
+                if in_box(key) && keep(key) {
+                    visit(points);
+                }
+            }
+            return;
+        }
+        let mut key = from.to_vec();
+        loop {
+            if keep(&key) {
+                if let Some(points) = self.cells.get(key.as_slice()) {
+                    visit(points);
+                }
+            }
+            // odometer over the keys of the box
+            let mut dimension = 0;
+            while dimension < key.len() && key[dimension] == to[dimension] {
+                key[dimension] = from[dimension];
+                dimension += 1;
+            }
+            if dimension == key.len() {
+                return;
+            }
+            key[dimension] += 1;
+        }
+    }
+
+    fn scan(&self, query: &[A]) -> Vec<(A, usize)> {
+        (0..self.data.len())
+            .map(|i| (D::distance_slice(query, self.data.row(i)), i))
+            .collect()
+    }
+}
+
+impl<A: LibData, D: DistanceMeasure<A>> NeighborIndex<A> for Grid<'_, A, D> {
+    fn backend(&self) -> IndexBackend {
+        IndexBackend::Grid
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn within(&self, query: &[A], radius: A) -> Result<Vec<(A, usize)>> {
+        let mut neighbors = vec![];
+        if let Some((from, to)) = self.key_box(query, radius) {
+            self.for_each_cell(
+                &from,
+                &to,
+                |_| true,
+                |points| {
+                    for i in points {
+                        let distance = D::distance_slice(query, self.data.row(*i));
+                        if distance <= radius {
+                            neighbors.push((distance, *i));
+                        }
+                    }
+                },
+            );
+        }
+        Ok(neighbors)
+    }
+
+    /// Visits the cells in growing rings around the cell of `query`, until no unvisited point
+    /// can be closer than the `k`-th nearest one found so far.
+    fn nearest(&self, query: &[A], k: usize) -> Result<Vec<(A, usize)>> {
+        let mut neighbors: Vec<(A, usize)> = vec![];
+        let center: Vec<i64> = query.iter().map(|q| cell_key(*q, self.cell_size)).collect();
+        if k == 0 {
+            return Ok(neighbors);
+        }
+        if k >= self.len() || query.iter().any(|q| !q.is_finite()) {
+            neighbors = self.scan(query);
+        } else {
+            let mut ring = center
+                .iter()
+                .zip(self.lower.iter().zip(self.upper.iter()))
+                .map(|(c, (l, u))| l.saturating_sub(*c).max(c.saturating_sub(*u)).max(0))
+                .max()
+                .unwrap_or(0);
+            loop {
+                let from: Vec<i64> = center
+                    .iter()
+                    .zip(self.lower.iter())
+                    .map(|(c, l)| c.saturating_sub(ring).max(*l))
+                    .collect();
+                let to: Vec<i64> = center
+                    .iter()
+                    .zip(self.upper.iter())
+                    .map(|(c, u)| c.saturating_add(ring).min(*u))
+                    .collect();
+                if box_size(&from, &to) > self.cells.len() as f64 {
+                    // fewer occupied cells than cells left to look up
+                    neighbors = self.scan(query);
+                    break;
+                }
+                let on_ring = |key: &[i64]| {
+                    key.iter()
+                        .zip(center.iter())
+                        .any(|(k, c)| k.saturating_sub(*c).saturating_abs() == ring)
+                };
+                self.for_each_cell(&from, &to, on_ring, |points| {
+                    neighbors.extend(
+                        points
+                            .iter()
+                            .map(|i| (D::distance_slice(query, self.data.row(*i)), *i)),
+                    )
+                });
+
+                let covers_all = from.iter().zip(self.lower.iter()).all(|(f, l)| f == l)
+                    && to.iter().zip(self.upper.iter()).all(|(t, u)| t == u);
+                if covers_all {
+                    break;
+                }
+                // every unvisited point lies beyond the ring along some dimension
+                let ring_a = A::from_i64(ring).unwrap();
+                let bound = query
+                    .iter()
+                    .zip(center.iter())
+                    .map(|(q, c)| {
+                        let cell_start = A::from_i64(*c).unwrap() * self.cell_size;
+                        let below = *q - (cell_start - ring_a * self.cell_size);
+                        let above = cell_start + (ring_a + A::one()) * self.cell_size - *q;
+                        below.min(above)
+                    })
+                    .fold(A::infinity(), A::min);
+                if neighbors.len() >= k {
+                    neighbors.select_nth_unstable_by(k - 1, compare_neighbors);
+                    neighbors.truncate(k);
+                    if neighbors[k - 1].0 <= bound {
+                        break;
+                    }
+                }
+                ring += 1;
+            }
+        }
+
+        let k = k.min(neighbors.len());
+        if k > 0 && k < neighbors.len() {
+            neighbors.select_nth_unstable_by(k - 1, compare_neighbors);
+        }
+        neighbors.truncate(k);
+        neighbors.sort_by(compare_neighbors);
+        Ok(neighbors)
+    }
+}
+
+/// Number of keys in the box from `from` to `to`.
+fn box_size(from: &[i64], to: &[i64]) -> f64 {
+    from.iter()
+        .zip(to.iter())
+        .map(|(f, t)| (*t as f64) - (*f as f64) + 1.0)
+        .product()
+}
+
+/// Cell of the coordinate `x`, saturating at the range of `i64`.
+fn cell_key<A: LibData>(x: A, cell_size: A) -> i64 {
+    let key = (x / cell_size).floor();
+    key.to_i64()
+        .unwrap_or(if key > A::zero() { i64::MAX } else { i64::MIN })
+}
+
+fn fitting_cell_size<A: LibData>(data: ArrayView2<A>) -> A {
+    let n_cells_per_dimension = A::from_usize(data.shape()[0].max(1))
+        .unwrap()
+        .powf(A::one() / A::from_usize(data.shape()[1].max(1)).unwrap())
+        .ceil();
+    let extent = data
+        .axis_iter(Axis(1))
+        .map(|column| {
+            let (min, max) = column
+                .iter()
+                .fold((A::infinity(), A::neg_infinity()), |(min, max), x| {
+                    (min.min(*x), max.max(*x))
+                });
+            max - min
+        })
+        .fold(A::zero(), A::max);
+    let cell_size = extent / n_cells_per_dimension;
+    if cell_size.is_finite() && cell_size > A::zero() {
+        cell_size
+    } else {
+        A::one()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distance_measure::{Chebyshev, Euclidean};
+    use crate::neighbors::BruteForce;
+    use ndarray::{arr2, Array2};
+
+    #[test]
+    fn test_grid_nearest_beyond_the_data() {
+        let data = arr2(&[[0.0, 0.0], [0.5, 0.1], [10.0, 10.0], [-3.0, 4.0]]);
+        for cell_size in [None, Some(0.1), Some(100.0)] {
+            let grid = Grid::<f64, Chebyshev>::new(data.view(), cell_size);
+            let exact = BruteForce::<f64, Chebyshev>::new(data.view());
+            for query in [[0.2, 0.0], [50.0, -20.0], [-3.0, 3.9]] {
+                for k in 1..=4 {
+                    assert_eq!(
+                        exact.nearest(&query, k).unwrap(),
+                        grid.nearest(&query, k).unwrap()
+                    );
+                }
+            }
+        }
+        let empty = Grid::<f64, Euclidean>::new(Array2::zeros((0, 2)), None);
+        assert!(empty.within(&[0.0, 0.0], 1.0).unwrap().is_empty());
+        assert!(empty.nearest(&[0.0, 0.0], 1).unwrap().is_empty());
+    }
+}
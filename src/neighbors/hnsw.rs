@@ -0,0 +1,350 @@
+use crate::distance_measure::DistanceMeasure;
+use crate::error::Result;
+use crate::neighbors::{compare_neighbors, Candidate, IndexBackend, NeighborIndex, Rows};
+use crate::params::ParameterError;
+use crate::utils::LibData;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+use std::marker::PhantomData;
+
+/// Largest number of points that are inserted into the graph at once.
+const MAX_BATCH: usize = 1024;
+
+/// Recall knobs of an `Hnsw` index.
+///
+/// Every point links to up to `m` neighbors on the upper layers and `2 * m` on the bottom
+/// layer. Insertions keep the `ef_construction` best candidates, queries the `ef_search` best,
+/// while they walk the graph. Larger values find more neighbors at the cost of time and memory.
+/// `seed` drives the random layer assignment, so the graph is the same for the same data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HnswParams {
+    pub m: usize,
+    pub ef_construction: usize,
+    pub ef_search: usize,
+    pub seed: u64,
+}
+
+impl Default for HnswParams {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 100,
+            ef_search: 64,
+            seed: 0,
+        }
+    }
+}
+
+impl HnswParams {
+    pub fn m(mut self, m: usize) -> Self {
+        self.m = m;
+        self
+    }
+
+    pub fn ef_construction(mut self, ef_construction: usize) -> Self {
+        self.ef_construction = ef_construction;
+        self
+    }
+
+    pub fn ef_search(mut self, ef_search: usize) -> Self {
+        self.ef_search = ef_search;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub(crate) fn validate(&self) -> std::result::Result<(), ParameterError> {
+        if self.m < 2 || self.ef_construction == 0 || self.ef_search == 0 {
+            return Err(ParameterError::InvalidHnswParams(*self));
+        }
+        Ok(())
+    }
+
+    /// Maximum number of links of a point on `layer`.
+    fn max_links(&self, layer: usize) -> usize {
+        if layer == 0 {
+            2 * self.m
+        } else {
+            self.m
+        }
+    }
+}
+
+/// Hierarchical navigable small world graph (Malkov and Yashunin, 2018).
+///
+/// Every point lives on the bottom layer and, with exponentially decreasing probability, on the
+/// layers above, which link far apart points. A query descends greedily from the top layer and
+/// searches the bottom layer with a beam of `ef_search` candidates. `within` then follows the
+/// links from all candidates inside the radius as long as they lead to points inside it.
+/// Neighbors that are not reachable this way are missed.
+///
+/// Points are inserted in batches of growing size. Each batch searches the graph built so far
+/// in parallel and is linked in row order, so the graph does not depend on the thread count.
+pub struct Hnsw<'a, A: LibData, D: DistanceMeasure<A>> {
+    data: Rows<'a, A>,
+    params: HnswParams,
+    /// Links of every point, per layer from the bottom up to the top layer of the point.
+    links: Vec<Vec<Vec<usize>>>,
+    entry: Option<usize>,
+    top_layer: usize,
+    distance_measure: PhantomData<D>,
+}
+
+impl<'a, A: LibData, D: DistanceMeasure<A>> Hnsw<'a, A, D> {
+    pub fn new(data: impl Into<Rows<'a, A>>, params: HnswParams) -> Self {
+        let data = data.into();
+        let n_rows = data.len();
+        let mut rng = StdRng::seed_from_u64(params.seed);
+        let level_scale = 1.0 / (params.m.max(2) as f64).ln();
+        let links = (0..n_rows)
+            .map(|_| {
+                let u: f64 = rng.gen();
+                let layer = (-(1.0 - u).ln() * level_scale).floor() as usize;
+                vec![vec![]; layer + 1]
+            })
+            .collect();
+        let mut index = Self {
+            data,
+            params,
+            links,
+            entry: None,
+            top_layer: 0,
+            distance_measure: PhantomData,
+        };
+        if n_rows == 0 {
+            return index;
+        }
+
+        index.entry = Some(0);
+        index.top_layer = index.links[0].len() - 1;
+        let mut start = 1;
+        while start < n_rows {
+            let end = (start + (start / 8).clamp(1, MAX_BATCH)).min(n_rows);
+            let plans: Vec<Vec<Vec<usize>>> = (start..end)
+                .into_par_iter()
+                .map(|point| index.plan(point))
+                .collect();
+            for (point, plan) in (start..end).zip(plans) {
+                index.link(point, plan);
+            }
+            start = end;
+        }
+        index
+    }
+
+    fn distance(&self, query: &[A], i: usize) -> A {
+        D::distance_slice(query, self.data.row(i))
+    }
+
+    /// The `ef` points closest to `query` on `layer` that the search from `entries` reaches,
+    /// ordered by increasing distance.
+    fn search_layer(
+        &self,
+        query: &[A],
+        entries: Vec<(A, usize)>,
+        ef: usize,
+        layer: usize,
+    ) -> Vec<(A, usize)> {
+        let mut visited: HashSet<usize> = entries.iter().map(|(_, i)| *i).collect();
+        let mut candidates: BinaryHeap<Reverse<Candidate<A>>> =
+            entries.iter().map(|e| Reverse(Candidate(*e))).collect();
+        let mut found: BinaryHeap<Candidate<A>> = entries.into_iter().map(Candidate).collect();
+        while found.len() > ef {
+            found.pop();
+        }
+
+        while let Some(Reverse(Candidate((distance, point)))) = candidates.pop() {
+            if found.len() >= ef && found.peek().is_some_and(|worst| distance > worst.0 .0) {
+                break;
+            }
+            for neighbor in &self.links[point][layer] {
+                if !visited.insert(*neighbor) {
+                    continue;
+                }
+                let d = self.distance(query, *neighbor);
+                if found.len() < ef || found.peek().is_none_or(|worst| d < worst.0 .0) {
+                    candidates.push(Reverse(Candidate((d, *neighbor))));
+                    found.push(Candidate((d, *neighbor)));
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+        found.into_sorted_vec().into_iter().map(|c| c.0).collect()
+    }
+
+    /// Greedy descent from the entry point to the bottom layer, followed by a search with `ef`
+    /// candidates there.
+    fn search(&self, query: &[A], ef: usize) -> Vec<(A, usize)> {
+        let entry = match self.entry {
+            Some(entry) => entry,
+            None => return vec![],
+        };
+        let mut entries = vec![(self.distance(query, entry), entry)];
+        for layer in (1..=self.top_layer).rev() {
+            entries = self.search_layer(query, entries, 1, layer);
+        }
+        self.search_layer(query, entries, ef, 0)
+    }
+
+    /// Picks up to `m` of the `candidates` around a point, ordered by increasing distance to it,
+    /// preferring those that are closer to the point than to every picked one, so the links
+    /// spread into different directions.
+    fn select(&self, candidates: &[(A, usize)], m: usize) -> Vec<usize> {
+        let mut selected: Vec<usize> = Vec::with_capacity(m);
+        let mut skipped = vec![];
+        for (distance, candidate) in candidates {
+            if selected.len() >= m {
+                break;
+            }
+            let row = self.data.row(*candidate);
+            if selected.iter().all(|s| self.distance(row, *s) > *distance) {
+                selected.push(*candidate);
+            } else {
+                skipped.push(*candidate);
+            }
+        }
+        let missing = m.saturating_sub(selected.len());
+        selected.extend(skipped.into_iter().take(missing));
+        selected
+    }
+
+    /// Links of `point` on every layer it shares with the current graph.
+    fn plan(&self, point: usize) -> Vec<Vec<usize>> {
+        let query = self.data.row(point);
+        let point_layer = self.links[point].len() - 1;
+        let entry = self.entry.unwrap();
+        let mut entries = vec![(self.distance(query, entry), entry)];
+        for layer in (point_layer + 1..=self.top_layer).rev() {
+            entries = self.search_layer(query, entries, 1, layer);
+        }
+        let mut plan = vec![vec![]; point_layer + 1];
+        for layer in (0..=point_layer.min(self.top_layer)).rev() {
+            entries = self.search_layer(query, entries, self.params.ef_construction, layer);
+            plan[layer] = self.select(&entries, self.params.m);
+        }
+        plan
+    }
+
+    /// Adds the links of `plan` in both directions, pruning neighbors that end up with too many.
+    fn link(&mut self, point: usize, plan: Vec<Vec<usize>>) {
+        for (layer, neighbors) in plan.iter().enumerate() {
+            let max_links = self.params.max_links(layer);
+            for neighbor in neighbors {
+                self.links[*neighbor][layer].push(point);
+                if self.links[*neighbor][layer].len() > max_links {
+                    let row = self.data.row(*neighbor);
+                    let mut candidates: Vec<(A, usize)> = self.links[*neighbor][layer]
+                        .iter()
+                        .map(|i| (self.distance(row, *i), *i))
+                        .collect();
+                    candidates.sort_by(compare_neighbors);
+                    self.links[*neighbor][layer] = self.select(&candidates, max_links);
+                }
+            }
+        }
+        let point_layer = plan.len() - 1;
+        self.links[point] = plan;
+        if point_layer > self.top_layer {
+            self.top_layer = point_layer;
+            self.entry = Some(point);
+        }
+    }
+}
+
+impl<A: LibData, D: DistanceMeasure<A>> NeighborIndex<A> for Hnsw<'_, A, D> {
+    fn backend(&self) -> IndexBackend {
+        IndexBackend::Hnsw(self.params)
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn within(&self, query: &[A], radius: A) -> Result<Vec<(A, usize)>> {
+        let candidates = self.search(query, self.params.ef_search);
+        let mut visited: HashSet<usize> = candidates.iter().map(|(_, i)| *i).collect();
+        let mut neighbors: Vec<(A, usize)> = candidates
+            .into_iter()
+            .filter(|(d, _)| *d <= radius)
+            .collect();
+        let mut stack: Vec<usize> = neighbors.iter().map(|(_, i)| *i).collect();
+        while let Some(point) = stack.pop() {
+            for neighbor in &self.links[point][0] {
+                if !visited.insert(*neighbor) {
+                    continue;
+                }
+                let d = self.distance(query, *neighbor);
+                if d <= radius {
+                    neighbors.push((d, *neighbor));
+                    stack.push(*neighbor);
+                }
+            }
+        }
+        Ok(neighbors)
+    }
+
+    fn nearest(&self, query: &[A], k: usize) -> Result<Vec<(A, usize)>> {
+        let mut neighbors = self.search(query, self.params.ef_search.max(k));
+        neighbors.truncate(k);
+        Ok(neighbors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distance_measure::Euclidean;
+    use crate::neighbors::BruteForce;
+    use ndarray::Array2;
+
+    #[test]
+    fn test_hnsw_recall() {
+        let data = Array2::from_shape_fn([2000, 16], |(i, j)| ((i * 16 + j) as f64 * 0.7).sin());
+        let exact = BruteForce::<f64, Euclidean>::new(data.view());
+        let index = Hnsw::<f64, Euclidean>::new(data.view(), HnswParams::default());
+        assert_eq!(2000, index.len());
+
+        let (mut hits, mut total) = (0, 0);
+        for row in data.outer_iter().step_by(13) {
+            let query = row.to_vec();
+            let expected: HashSet<usize> = exact
+                .within(&query, 2.5)
+                .unwrap()
+                .into_iter()
+                .map(|(_, i)| i)
+                .collect();
+            let found = index.within(&query, 2.5).unwrap();
+            assert!(found.iter().all(|(d, i)| *d <= 2.5 && expected.contains(i)));
+            hits += found.len();
+            total += expected.len();
+
+            let nearest = index.nearest(&query, 10).unwrap();
+            assert_eq!(10, nearest.len());
+            assert_eq!(exact.nearest(&query, 1).unwrap()[0].1, nearest[0].1);
+        }
+        assert!(total > 0);
+        assert!(hits as f64 >= 0.95 * total as f64);
+    }
+
+    #[test]
+    fn test_hnsw_is_deterministic() {
+        let data = Array2::from_shape_fn([500, 4], |(i, j)| ((i * 4 + j) as f64 * 1.3).cos());
+        let params = HnswParams::default().m(4).ef_construction(20);
+        let a = Hnsw::<f64, Euclidean>::new(data.view(), params);
+        let b = Hnsw::<f64, Euclidean>::new(data.view(), params);
+        assert_eq!(a.links, b.links);
+        assert_eq!(a.entry, b.entry);
+        assert_eq!(
+            Err(ParameterError::InvalidHnswParams(params.m(1))),
+            params.m(1).validate()
+        );
+    }
+}
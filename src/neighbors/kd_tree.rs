@@ -0,0 +1,82 @@
+use crate::distance_measure::DistanceMeasure;
+use crate::error::Result;
+use crate::neighbors::{IndexBackend, NeighborIndex, RowData, Rows};
+use crate::utils::{LibData, RefArray};
+use kdtree::KdTree;
+use ndarray::Axis;
+use std::marker::PhantomData;
+
+/// KD-tree of the `kdtree` crate over the rows, which refers to borrowed rows and shares the
+/// rows of owned data.
+pub struct KdTreeIndex<'a, A: LibData, D: DistanceMeasure<A>> {
+    tree: KdTree<A, usize, Row<'a, A>>,
+    distance_measure: PhantomData<D>,
+}
+
+enum Row<'a, A: LibData> {
+    Borrowed(&'a [A]),
+    Owned(RefArray<A>),
+}
+
+impl<A: LibData> AsRef<[A]> for Row<'_, A> {
+    fn as_ref(&self) -> &[A] {
+        match self {
+            Self::Borrowed(row) => row,
+            Self::Owned(row) => row.as_ref(),
+        }
+    }
+}
+
+impl<'a, A: LibData, D: DistanceMeasure<A>> KdTreeIndex<'a, A, D> {
+    pub fn new(data: impl Into<Rows<'a, A>>) -> Result<Self> {
+        let data = data.into();
+        let n_columns = data.n_columns();
+        let mut tree = KdTree::new(n_columns);
+        match data.0 {
+            RowData::Borrowed(data) => {
+                let rows = data.to_slice().unwrap();
+                for i in 0..data.shape()[0] {
+                    tree.add(Row::Borrowed(&rows[i * n_columns..(i + 1) * n_columns]), i)?;
+                }
+            }
+            RowData::Owned(data) => {
+                for i in 0..data.shape()[0] {
+                    let row = data.clone().index_axis_move(Axis(0), i);
+                    tree.add(Row::Owned(RefArray(row)), i)?;
+                }
+            }
+        }
+        Ok(Self {
+            tree,
+            distance_measure: PhantomData,
+        })
+    }
+}
+
+impl<A: LibData, D: DistanceMeasure<A>> NeighborIndex<A> for KdTreeIndex<'_, A, D> {
+    fn backend(&self) -> IndexBackend {
+        IndexBackend::KdTree
+    }
+
+    fn len(&self) -> usize {
+        self.tree.size()
+    }
+
+    fn within(&self, query: &[A], radius: A) -> Result<Vec<(A, usize)>> {
+        Ok(self
+            .tree
+            .within(query, radius, &D::distance_slice)?
+            .into_iter()
+            .map(|(d, i)| (d, *i))
+            .collect())
+    }
+
+    fn nearest(&self, query: &[A], k: usize) -> Result<Vec<(A, usize)>> {
+        Ok(self
+            .tree
+            .nearest(query, k, &D::distance_slice)?
+            .into_iter()
+            .map(|(d, i)| (d, *i))
+            .collect())
+    }
+}
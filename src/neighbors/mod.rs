@@ -0,0 +1,337 @@
+use crate::distance_measure::DistanceMeasure;
+use crate::error::Result;
+use crate::params::ParameterError;
+use crate::utils::LibData;
+use ndarray::{ArcArray2, Array2, ArrayView2};
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+pub mod blocked;
+pub mod brute_force;
+pub mod grid;
+pub mod hnsw;
+pub mod kd_tree;
+pub mod vp_tree;
+
+pub use blocked::Blocked;
+pub use brute_force::BruteForce;
+pub use grid::Grid;
+pub use hnsw::{Hnsw, HnswParams};
+pub use kd_tree::KdTreeIndex;
+pub use vp_tree::VpTree;
+
+/// Neighbor search over the rows of a dataset under a `DistanceMeasure`.
+///
+/// Neighbors are returned as pairs of their distance to the query and their row index.
+pub trait NeighborIndex<A: LibData>: Send + Sync {
+    fn backend(&self) -> IndexBackend;
+
+    /// Number of indexed points.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// All points at most `radius` away from `query`, in no particular order.
+    fn within(&self, query: &[A], radius: A) -> Result<Vec<(A, usize)>>;
+
+    /// The `k` points closest to `query`, ordered by increasing distance.
+    fn nearest(&self, query: &[A], k: usize) -> Result<Vec<(A, usize)>>;
+}
+
+/// Rows indexed by a `NeighborIndex`: borrowed from the caller if they lie in memory one
+/// after the other, and otherwise copied into an array of the index's own, which may be shared
+/// with other owners. Only built through `From`, which ensures that the rows are in standard
+/// layout.
+pub struct Rows<'a, A: LibData>(RowData<'a, A>);
+
+enum RowData<'a, A: LibData> {
+    Borrowed(ArrayView2<'a, A>),
+    Owned(ArcArray2<A>),
+}
+
+impl<'a, A: LibData> From<ArrayView2<'a, A>> for Rows<'a, A> {
+    fn from(data: ArrayView2<'a, A>) -> Self {
+        Self(if data.is_standard_layout() {
+            RowData::Borrowed(data)
+        } else {
+            RowData::Owned(data.as_standard_layout().into_owned().into_shared())
+        })
+    }
+}
+
+impl<A: LibData> From<Array2<A>> for Rows<'_, A> {
+    fn from(data: Array2<A>) -> Self {
+        Self::from(data.into_shared())
+    }
+}
+
+impl<A: LibData> From<ArcArray2<A>> for Rows<'_, A> {
+    fn from(data: ArcArray2<A>) -> Self {
+        Self(RowData::Owned(if data.is_standard_layout() {
+            data
+        } else {
+            data.as_standard_layout().into_owned().into_shared()
+        }))
+    }
+}
+
+impl<A: LibData> Rows<'_, A> {
+    pub fn view(&self) -> ArrayView2<'_, A> {
+        match &self.0 {
+            RowData::Borrowed(data) => data.view(),
+            RowData::Owned(data) => data.view(),
+        }
+    }
+
+    /// All rows one after the other.
+    pub fn as_slice(&self) -> &[A] {
+        match &self.0 {
+            RowData::Borrowed(data) => data.to_slice().unwrap(),
+            RowData::Owned(data) => data.as_slice().unwrap(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.view().shape()[0]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn n_columns(&self) -> usize {
+        self.view().shape()[1]
+    }
+
+    pub fn row(&self, i: usize) -> &[A] {
+        let n_columns = self.n_columns();
+        &self.as_slice()[i * n_columns..(i + 1) * n_columns]
+    }
+}
+
+/// Data structures behind a `NeighborIndex`.
+///
+/// Which of them find all neighbors depends on the distance measure, so every
+/// `DistanceMeasure` lists the backends that are valid for it in `INDEX_BACKENDS`. `Hnsw` may
+/// miss neighbors under any measure and is only used when requested.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexBackend {
+    /// KD-tree, which prunes with axis-aligned bounding boxes and therefore needs a Minkowski
+    /// metric.
+    KdTree,
+    /// Vantage-point tree, which prunes with the triangle inequality and therefore needs a
+    /// metric.
+    VpTree,
+    /// Linear scan over all points, exact for any distance measure.
+    BruteForce,
+    /// Linear scan over tiles of points in parallel with vectorized distance kernels, exact for
+    /// any distance measure.
+    Blocked,
+    /// Uniform grid with cells of the bandwidth, which needs a distance that is at least the
+    /// difference along every dimension.
+    Grid,
+    /// Hierarchical navigable small world graph, which only visits the neighborhood of the
+    /// query in the graph and may therefore miss neighbors. `HnswParams` trade recall for speed.
+    Hnsw(HnswParams),
+}
+
+/// Number of dimensions from which `MeanShift` scans with `IndexBackend::Blocked` instead of
+/// a tree, if the distance measure allows it and no backend is requested.
+pub const BLOCKED_MIN_DIMENSIONS: usize = 32;
+
+/// Number of dimensions up to which `MeanShift` indexes with `IndexBackend::Grid` instead of a
+/// tree, if the distance measure allows it and no backend is requested.
+pub const GRID_MAX_DIMENSIONS: usize = 3;
+
+impl Display for IndexBackend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::KdTree => write!(f, "kd-tree"),
+            Self::VpTree => write!(f, "vp-tree"),
+            Self::BruteForce => write!(f, "brute force"),
+            Self::Blocked => write!(f, "blocked brute force"),
+            Self::Grid => write!(f, "grid"),
+            Self::Hnsw(_) => write!(f, "hnsw"),
+        }
+    }
+}
+
+impl IndexBackend {
+    /// Returns `requested` if `D` supports it, or if it is a valid `Hnsw`. Otherwise, data with
+    /// `n_columns` dimensions is hashed into a `Grid` up to `GRID_MAX_DIMENSIONS` dimensions,
+    /// scanned with `Blocked` from `BLOCKED_MIN_DIMENSIONS` dimensions on, and indexed with the
+    /// first backend `D` lists in between.
+    pub(crate) fn select<A: LibData, D: DistanceMeasure<A>>(
+        requested: Option<IndexBackend>,
+        n_columns: usize,
+    ) -> std::result::Result<IndexBackend, ParameterError> {
+        match requested {
+            Some(IndexBackend::Hnsw(params)) => {
+                params.validate()?;
+                Ok(IndexBackend::Hnsw(params))
+            }
+            Some(backend) if D::INDEX_BACKENDS.contains(&backend) => Ok(backend),
+            Some(backend) => Err(ParameterError::UnsupportedIndexBackend {
+                backend,
+                distance_measure: D::NAME,
+            }),
+            None if n_columns <= GRID_MAX_DIMENSIONS
+                && D::INDEX_BACKENDS.contains(&IndexBackend::Grid) =>
+            {
+                Ok(IndexBackend::Grid)
+            }
+            None if n_columns >= BLOCKED_MIN_DIMENSIONS
+                && D::INDEX_BACKENDS.contains(&IndexBackend::Blocked) =>
+            {
+                Ok(IndexBackend::Blocked)
+            }
+            None => Ok(D::INDEX_BACKENDS[0]),
+        }
+    }
+
+    /// Indexes the rows of `data`, borrowing them for the lifetime of the index unless they
+    /// have to be copied (see `Rows`). `cell_size` is the cell size of a `Grid`, usually the
+    /// bandwidth; without it, the grid fits its cells to the data.
+    pub(crate) fn build<'a, A: LibData, D: DistanceMeasure<A>>(
+        self,
+        data: impl Into<Rows<'a, A>>,
+        cell_size: Option<A>,
+    ) -> Result<Arc<dyn NeighborIndex<A> + 'a>> {
+        let data = data.into();
+        Ok(match self {
+            Self::KdTree => Arc::new(KdTreeIndex::<A, D>::new(data)?),
+            Self::VpTree => Arc::new(VpTree::<A, D>::new(data)),
+            Self::BruteForce => Arc::new(BruteForce::<A, D>::new(data)),
+            Self::Blocked => Arc::new(Blocked::<A, D>::new(data)),
+            Self::Grid => Arc::new(Grid::<A, D>::new(data, cell_size)),
+            Self::Hnsw(params) => Arc::new(Hnsw::<A, D>::new(data, params)),
+        })
+    }
+}
+
+/// Orders neighbors by distance and then by row index.
+pub(crate) fn compare_neighbors<A: LibData>(a: &(A, usize), b: &(A, usize)) -> Ordering {
+    a.0.partial_cmp(&b.0)
+        .unwrap_or(Ordering::Equal)
+        .then(a.1.cmp(&b.1))
+}
+
+/// Neighbor ordered by distance and row index, for the heaps of the graph and tree searches.
+pub(crate) struct Candidate<A: LibData>(pub (A, usize));
+
+impl<A: LibData> PartialEq for Candidate<A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<A: LibData> Eq for Candidate<A> {}
+
+impl<A: LibData> PartialOrd for Candidate<A> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<A: LibData> Ord for Candidate<A> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_neighbors(&self.0, &other.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distance_measure::{Chebyshev, Euclidean, Manhattan, DTW};
+    use crate::test_utils::{close_l1, read_data};
+    use ndarray::{Array2, ShapeBuilder};
+
+    fn sorted<A: LibData>(mut neighbors: Vec<(A, usize)>) -> Vec<usize> {
+        neighbors.sort_by(compare_neighbors);
+        neighbors.into_iter().map(|(_, i)| i).collect()
+    }
+
+    fn assert_agrees_with_brute_force<D: DistanceMeasure<f64>>(data: ArrayView2<f64>, radius: f64) {
+        let exact = BruteForce::<f64, D>::new(data);
+        for backend in D::INDEX_BACKENDS {
+            let index = backend.build::<f64, D>(data, Some(radius)).unwrap();
+            assert_eq!(*backend, index.backend());
+            assert_eq!(data.shape()[0], index.len());
+            for row in data.outer_iter().step_by(7) {
+                let query = row.to_vec();
+                assert_eq!(
+                    sorted(exact.within(&query, radius).unwrap()),
+                    sorted(index.within(&query, radius).unwrap())
+                );
+                let nearest = index.nearest(&query, 5).unwrap();
+                for (a, b) in exact.nearest(&query, 5).unwrap().iter().zip(nearest.iter()) {
+                    assert_eq!(a.1, b.1);
+                    close_l1(a.0, b.0, 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_backends_agree_with_brute_force() {
+        let data = read_data::<f64>("data/test.csv");
+        assert_agrees_with_brute_force::<Euclidean>(data.view(), 1.0);
+        assert_agrees_with_brute_force::<Manhattan>(data.view(), 1.0);
+        assert_agrees_with_brute_force::<Chebyshev>(data.view(), 0.5);
+        assert_agrees_with_brute_force::<DTW>(data.view(), 1.0);
+
+        // more rows than fit into a tile of `Blocked`, in more dimensions than one lane
+        let data = Array2::from_shape_fn([600, 45], |(i, j)| ((i * 45 + j) as f64 * 0.7).sin());
+        assert_agrees_with_brute_force::<Euclidean>(data.view(), 5.0);
+        assert_agrees_with_brute_force::<Manhattan>(data.view(), 30.0);
+
+        // rows that do not lie one after the other are copied
+        let columns = data.t().to_owned();
+        assert_agrees_with_brute_force::<Euclidean>(columns.t(), 5.0);
+        let mut fortran = Array2::zeros(data.raw_dim().f());
+        fortran.assign(&data);
+        let query = data.row(3).to_vec();
+        assert_eq!(
+            BruteForce::<f64, Euclidean>::new(data.view()).nearest(&query, 4),
+            BruteForce::<f64, Euclidean>::new(fortran).nearest(&query, 4)
+        );
+    }
+
+    #[test]
+    fn test_select_backend() {
+        assert_eq!(
+            IndexBackend::select::<f64, Euclidean>(None, 3),
+            Ok(IndexBackend::Grid)
+        );
+        assert_eq!(
+            IndexBackend::select::<f64, Chebyshev>(None, 2),
+            Ok(IndexBackend::Grid)
+        );
+        assert_eq!(
+            IndexBackend::select::<f64, Euclidean>(None, 4),
+            Ok(IndexBackend::KdTree)
+        );
+        assert_eq!(
+            IndexBackend::select::<f64, Euclidean>(None, 128),
+            Ok(IndexBackend::Blocked)
+        );
+        assert_eq!(
+            IndexBackend::select::<f64, Euclidean>(Some(IndexBackend::VpTree), 128),
+            Ok(IndexBackend::VpTree)
+        );
+        assert_eq!(
+            IndexBackend::select::<f64, DTW>(None, 3),
+            Ok(IndexBackend::BruteForce)
+        );
+        assert_eq!(
+            IndexBackend::select::<f64, DTW>(Some(IndexBackend::KdTree), 3),
+            Err(ParameterError::UnsupportedIndexBackend {
+                backend: IndexBackend::KdTree,
+                distance_measure: "dtw"
+            })
+        );
+    }
+}
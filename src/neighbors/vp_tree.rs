@@ -0,0 +1,156 @@
+use crate::distance_measure::DistanceMeasure;
+use crate::error::Result;
+use crate::neighbors::{compare_neighbors, Candidate, IndexBackend, NeighborIndex, Rows};
+use crate::utils::LibData;
+use std::collections::BinaryHeap;
+use std::marker::PhantomData;
+
+/// Vantage-point tree. Every node splits the points below it by their distance to its point
+/// at the median distance, so whole subtrees can be skipped with the triangle inequality.
+pub struct VpTree<'a, A: LibData, D: DistanceMeasure<A>> {
+    data: Rows<'a, A>,
+    nodes: Vec<Node<A>>,
+    root: Option<usize>,
+    distance_measure: PhantomData<D>,
+}
+
+struct Node<A: LibData> {
+    point: usize,
+    /// Points in `inside` are at most, points in `outside` at least this far from `point`.
+    threshold: A,
+    inside: Option<usize>,
+    outside: Option<usize>,
+}
+
+impl<'a, A: LibData, D: DistanceMeasure<A>> VpTree<'a, A, D> {
+    pub fn new(data: impl Into<Rows<'a, A>>) -> Self {
+        let data = data.into();
+        let mut points: Vec<usize> = (0..data.len()).collect();
+        let mut tree = Self {
+            data,
+            nodes: Vec::with_capacity(points.len()),
+            root: None,
+            distance_measure: PhantomData,
+        };
+        tree.root = tree.build(&mut points);
+        tree
+    }
+
+    fn build(&mut self, points: &mut [usize]) -> Option<usize> {
+        let (point, rest) = points.split_first_mut()?;
+        let point = *point;
+        let mut threshold = A::zero();
+        let (mut inside, mut outside) = (None, None);
+        if !rest.is_empty() {
+            let mut distances: Vec<(A, usize)> = rest
+                .iter()
+                .map(|i| {
+                    (
+                        D::distance_slice(self.data.row(point), self.data.row(*i)),
+                        *i,
+                    )
+                })
+                .collect();
+            let median = distances.len() / 2;
+            distances.select_nth_unstable_by(median, compare_neighbors);
+            threshold = distances[median].0;
+            for (slot, (_, i)) in rest.iter_mut().zip(distances) {
+                *slot = i;
+            }
+            let (near, far) = rest.split_at_mut(median + 1);
+            inside = self.build(near);
+            outside = self.build(far);
+        }
+        self.nodes.push(Node {
+            point,
+            threshold,
+            inside,
+            outside,
+        });
+        Some(self.nodes.len() - 1)
+    }
+
+    fn search_nearest(
+        &self,
+        node: usize,
+        query: &[A],
+        k: usize,
+        heap: &mut BinaryHeap<Candidate<A>>,
+    ) {
+        let node = &self.nodes[node];
+        let distance = D::distance_slice(query, self.data.row(node.point));
+        let candidate = Candidate((distance, node.point));
+        if heap.len() < k {
+            heap.push(candidate);
+        } else if candidate < *heap.peek().unwrap() {
+            heap.pop();
+            heap.push(candidate);
+        }
+
+        let tau = |heap: &BinaryHeap<Candidate<A>>| match heap.peek() {
+            Some(farthest) if heap.len() >= k => (farthest.0).0,
+            _ => A::INFINITY,
+        };
+        let (first, second) = if distance < node.threshold {
+            (node.inside, node.outside)
+        } else {
+            (node.outside, node.inside)
+        };
+        for child in [first, second].iter().flatten() {
+            let tau = tau(heap);
+            let reachable = if Some(*child) == node.inside {
+                distance - tau <= node.threshold
+            } else {
+                distance + tau >= node.threshold
+            };
+            if reachable {
+                self.search_nearest(*child, query, k, heap);
+            }
+        }
+    }
+}
+
+impl<A: LibData, D: DistanceMeasure<A>> NeighborIndex<A> for VpTree<'_, A, D> {
+    fn backend(&self) -> IndexBackend {
+        IndexBackend::VpTree
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn within(&self, query: &[A], radius: A) -> Result<Vec<(A, usize)>> {
+        let mut neighbors = vec![];
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            let distance = D::distance_slice(query, self.data.row(node.point));
+            if distance <= radius {
+                neighbors.push((distance, node.point));
+            }
+            if let Some(inside) = node.inside {
+                if distance - radius <= node.threshold {
+                    stack.push(inside);
+                }
+            }
+            if let Some(outside) = node.outside {
+                if distance + radius >= node.threshold {
+                    stack.push(outside);
+                }
+            }
+        }
+        Ok(neighbors)
+    }
+
+    fn nearest(&self, query: &[A], k: usize) -> Result<Vec<(A, usize)>> {
+        let mut heap = BinaryHeap::with_capacity(k + 1);
+        if let (Some(root), true) = (self.root, k > 0) {
+            self.search_nearest(root, query, k, &mut heap);
+        }
+        Ok(heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|candidate| candidate.0)
+            .collect())
+    }
+}
@@ -1,26 +1,168 @@
 #[cfg(test)]
 mod tests;
 
+use crate::bandwidth::Whitening;
 use crate::distance_measure::DistanceMeasure;
-use crate::utils::{LibData, RefArray, SliceComp};
-use anyhow::Result;
-use kdtree::KdTree;
+use crate::error::{MeanShiftError, Result};
+use crate::kernel::{Flat, Kernel};
+use crate::neighbors::{IndexBackend, NeighborIndex, Rows};
+use crate::params::{validate_bandwidth, MeanShiftParams, Variant};
+use crate::result::ClusteringResult;
+use crate::utils::{contiguous, validate_data, validate_weights, LibData, SliceComp, UnionFind};
 use log::debug;
-use ndarray::{Array1, Array2, ArrayView1, ArrayView2, Axis};
+use ndarray::{s, stack, ArcArray2, Array1, Array2, ArrayView1, ArrayView2, Axis, CowArray, Ix2};
 use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
 use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::env;
-use std::iter::FromIterator;
 use std::marker::PhantomData;
 use std::sync::Arc;
 
+/// Modes, their intensities and bandwidths, and the mode of every seed identifier.
+type CollectedMeans<A> = (Vec<Array1<A>>, Vec<usize>, Vec<A>, HashMap<usize, usize>);
+
+/// Final state of a single mean shift hill-climb.
+#[derive(Clone, Debug)]
+pub struct Trajectory<A: LibData> {
+    pub mean: Array1<A>,
+    /// Number of points with a positive kernel weight around `mean`.
+    pub points_within: usize,
+    /// Sum of the sample weights of these points.
+    pub intensity: A,
+    pub iterations: usize,
+    /// Bandwidth around `mean`: the global bandwidth, or in adaptive mode the average
+    /// bandwidth of the points within.
+    pub bandwidth: A,
+}
+
+/// Everything a trajectory needs to know besides its seed.
+pub struct ShiftContext<'a, A: LibData, K: Kernel> {
+    pub data: ArrayView2<'a, A>,
+    pub tree: &'a dyn NeighborIndex<A>,
+    pub bandwidth: A,
+    /// Distance within which the kernel of every point but the `wide_points` is positive.
+    pub radius: A,
+    /// Per-point bandwidths of the adaptive mode.
+    pub point_bandwidths: Option<&'a Array1<A>>,
+    /// Points of the adaptive mode whose kernel reaches beyond `radius`, which every mean
+    /// update compares with directly instead of querying the index for them.
+    pub wide_points: Vec<usize>,
+    /// Sample weights of the points.
+    pub weights: Option<ArrayView1<'a, A>>,
+    pub kernel: &'a K,
+    pub params: &'a MeanShiftParams<A>,
+    /// Converged trajectories of the earlier rounds of seeds, if the basin shortcut is enabled.
+    pub basins: Option<&'a Basins<A>>,
+}
+
+impl<A: LibData, K: Kernel> ShiftContext<'_, A, K> {
+    fn with_basins<'b>(&'b self, basins: &'b Basins<A>) -> ShiftContext<'b, A, K> {
+        ShiftContext {
+            data: self.data.reborrow(),
+            tree: self.tree,
+            bandwidth: self.bandwidth,
+            radius: self.radius,
+            point_bandwidths: self.point_bandwidths,
+            wide_points: self.wide_points.clone(),
+            weights: self.weights.map(|w| w.reborrow()),
+            kernel: self.kernel,
+            params: self.params,
+            basins: Some(basins),
+        }
+    }
+}
+
+/// Most seeds that climb at the same time with the basin shortcut, which only see the basins of
+/// the seeds before their round. The rounds start with a single seed and double in size, so that
+/// the first basins are known early.
+const MAX_BASIN_ROUND: usize = 256;
+
+/// Converged trajectories and the points that lay close to their paths, which are in the basin
+/// of attraction of the trajectory's mode. A trajectory that comes close to such a point stops
+/// and takes over the mode. The basins only change between the rounds of seeds, in the order of
+/// the seeds, so they do not depend on the scheduling of the threads.
+pub struct Basins<A: LibData> {
+    radius: A,
+    /// Index of the trajectory whose basin every point lies in.
+    owners: Vec<Option<usize>>,
+    trajectories: Vec<Trajectory<A>>,
+}
+
+impl<A: LibData> Basins<A> {
+    fn new(n_points: usize, radius: A) -> Self {
+        Self {
+            radius,
+            owners: vec![None; n_points],
+            trajectories: vec![],
+        }
+    }
+
+    /// Converged trajectory in whose basin one of the `points` lies.
+    fn lookup(&self, points: &[usize]) -> Option<(usize, &Trajectory<A>)> {
+        points
+            .iter()
+            .find_map(|i| self.owners[*i].map(|owner| (owner, &self.trajectories[owner])))
+    }
+
+    /// Puts the unassigned points visited by `climb` into the basin it ran into, or into the
+    /// basin of its own trajectory if it converged.
+    fn record(&mut self, climb: &Climb<A>) {
+        let id = match climb.basin {
+            Some(id) => id,
+            None if climb.converged => {
+                self.trajectories.push(climb.trajectory.clone());
+                self.trajectories.len() - 1
+            }
+            None => return,
+        };
+        for i in climb.visited.iter() {
+            self.owners[*i].get_or_insert(id);
+        }
+    }
+}
+
+/// Trajectory of a seed and the points within the basin radius of its path.
+pub struct Climb<A: LibData> {
+    pub trajectory: Trajectory<A>,
+    visited: Vec<usize>,
+    /// Converged trajectory whose basin the climb ran into.
+    basin: Option<usize>,
+    converged: bool,
+}
+
+/// New position of a mean after a single mean shift update.
+struct Step<A: LibData> {
+    mean: Array1<A>,
+    points_within: usize,
+    intensity: A,
+    bandwidth: A,
+    /// Points within the basin radius of the mean before the update.
+    nearby: Vec<usize>,
+}
+
+/// Label of points that lie outside the bandwidth of every mode when `cluster_all` is disabled.
+pub const NOISE_LABEL: i32 = -1;
+
 #[derive(Default)]
-pub struct MeanShift<A: LibData, D: DistanceMeasure<A>> {
+pub struct MeanShift<A: LibData, D: DistanceMeasure<A>, K: Kernel = Flat> {
     pub bandwidth: Option<A>,
+    /// Bandwidth of every point in adaptive mode.
+    pub point_bandwidths: Option<Array1<A>>,
     pub cluster_centers: Option<Array2<A>>,
-    pub tree: Option<Arc<KdTree<A, usize, RefArray<A>>>>,
-    pub center_tree: Option<KdTree<A, usize, RefArray<A>>>,
+    center_bandwidths: Vec<A>,
+    /// Index of `build_index` over `indexed_rows`, built by the first call to `cluster` if it
+    /// needs the bandwidth.
+    pub tree: Option<Arc<dyn NeighborIndex<A>>>,
+    pub center_tree: Option<Arc<dyn NeighborIndex<A>>>,
+    pub kernel: K,
+    pub params: MeanShiftParams<A>,
+    /// Map into the coordinates of an anisotropic bandwidth, in which the index is built.
+    whitening: Option<Whitening<A>>,
+    pool: Option<Arc<ThreadPool>>,
+    /// Copy of the rows of `build_index` in the coordinates of the bandwidth, which `tree`
+    /// shares and `cluster` compares its rows with.
+    indexed_rows: Option<ArcArray2<A>>,
+    indexed_whitening: Option<Whitening<A>>,
     distance_measure: PhantomData<D>,
 }
 
@@ -28,202 +170,761 @@ impl<A: LibData, D: DistanceMeasure<A>> MeanShift<A, D> {
     pub fn new(_distance_measure: D, bandwidth: Option<A>) -> Self {
         Self {
             bandwidth,
+            point_bandwidths: None,
             cluster_centers: None,
+            center_bandwidths: vec![],
             tree: None,
             center_tree: None,
-            distance_measure: PhantomData::default(),
+            kernel: Flat,
+            params: MeanShiftParams::default(),
+            whitening: None,
+            pool: None,
+            indexed_rows: None,
+            indexed_whitening: None,
+            distance_measure: PhantomData,
+        }
+    }
+
+    /// Runs the algorithm in a thread pool of its own with `n_threads` threads
+    /// (`0` lets rayon choose the number of threads).
+    pub fn new_with_threads(
+        distance_measure: D,
+        bandwidth: Option<A>,
+        n_threads: usize,
+    ) -> Result<Self> {
+        let pool = ThreadPoolBuilder::new().num_threads(n_threads).build()?;
+        Ok(Self::new(distance_measure, bandwidth).with_thread_pool(Arc::new(pool)))
+    }
+}
+
+impl<A: LibData, D: DistanceMeasure<A>, K: Kernel> MeanShift<A, D, K> {
+    pub fn with_kernel<K2: Kernel>(self, kernel: K2) -> MeanShift<A, D, K2> {
+        MeanShift {
+            bandwidth: self.bandwidth,
+            point_bandwidths: self.point_bandwidths,
+            cluster_centers: self.cluster_centers,
+            center_bandwidths: self.center_bandwidths,
+            tree: self.tree,
+            center_tree: self.center_tree,
+            kernel,
+            params: self.params,
+            whitening: self.whitening,
+            pool: self.pool,
+            indexed_rows: self.indexed_rows,
+            indexed_whitening: self.indexed_whitening,
+            distance_measure: PhantomData,
+        }
+    }
+
+    /// Runs the algorithm inside `pool` instead of the global rayon pool.
+    pub fn with_thread_pool(mut self, pool: Arc<ThreadPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    fn install<R: Send>(&mut self, op: impl FnOnce(&mut Self) -> R + Send) -> R {
+        match self.pool.clone() {
+            Some(pool) => pool.install(|| op(self)),
+            None => op(self),
+        }
+    }
+
+    pub fn with_params(mut self, params: MeanShiftParams<A>) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Builds a neighbor index over a copy of `data`, which `cluster` reuses for as long as it
+    /// is called with the same rows, e.g. to try out several bandwidths. Otherwise, every call
+    /// to `cluster` indexes the rows it is given without copying them, unless they are not
+    /// contiguous. A grid needs the bandwidth for its cells, so without one it is only built
+    /// by the first call to `cluster`.
+    pub fn build_index(&mut self, data: ArrayView2<A>) -> Result<()> {
+        self.install(|this| {
+            this.whitening = this.params.anisotropy.whitening(data, None)?;
+            let rows = this.whiten(data).into_owned().into_shared();
+            let backend = this.index_backend(data.shape()[1])?;
+            this.tree = match (backend, this.bandwidth) {
+                (IndexBackend::Grid, None) => None,
+                _ => Some(this.new_index(rows.clone())?),
+            };
+            this.indexed_rows = Some(rows);
+            this.indexed_whitening = this.whitening.clone();
+            Ok(())
+        })
+    }
+
+    /// The index of `build_index` if it was built for the same rows, the same bandwidth shape
+    /// and the same backend, and otherwise an index that borrows the rows of `whitened`, which
+    /// are the rows to cluster in the coordinates of the bandwidth. If only the backend differs
+    /// or the index is yet to be built, it is built over the rows of `build_index` and kept.
+    fn index<'a>(&mut self, whitened: ArrayView2<'a, A>) -> Result<Arc<dyn NeighborIndex<A> + 'a>> {
+        let rows = match &self.indexed_rows {
+            Some(rows) if self.indexed_whitening == self.whitening && rows == whitened => {
+                rows.clone()
+            }
+            _ => return self.new_index(whitened),
+        };
+        let backend = self.index_backend(whitened.shape()[1])?;
+        match &self.tree {
+            Some(tree) if tree.backend() == backend => {
+                debug!("Reusing the neighbor index of build_index.");
+                Ok(tree.clone())
+            }
+            _ => {
+                let tree = self.new_index(rows)?;
+                self.tree = Some(tree.clone());
+                Ok(tree)
+            }
         }
     }
 
-    pub fn new_with_threads(_distance_measure: D, bandwidth: Option<A>, n_threads: usize) -> Self {
-        env::set_var("RAYON_NUM_THREADS", n_threads.to_string());
-        Self::new(_distance_measure, bandwidth)
+    fn index_backend(&self, n_columns: usize) -> Result<IndexBackend> {
+        Ok(IndexBackend::select::<A, D>(
+            self.params.index_backend,
+            n_columns,
+        )?)
     }
 
-    fn build_center_tree(&mut self, data: ArrayView2<A>) {
-        let columns = data.shape()[1];
-        self.center_tree = Some(KdTree::new(columns));
+    /// Indexes `data` with the selected backend, with grid cells of the bandwidth once it is
+    /// known.
+    fn new_index<'a>(
+        &self,
+        data: impl Into<Rows<'a, A>>,
+    ) -> Result<Arc<dyn NeighborIndex<A> + 'a>> {
+        let data = data.into();
+        self.index_backend(data.n_columns())?
+            .build::<A, D>(data, self.bandwidth)
+    }
+
+    /// Transforms `data` into the coordinates of the bandwidth, borrowing it if the bandwidth
+    /// is isotropic.
+    fn whiten<'a>(&self, data: ArrayView2<'a, A>) -> CowArray<'a, A, Ix2> {
+        match &self.whitening {
+            Some(whitening) => CowArray::from(whitening.whiten(data)),
+            None => CowArray::from(data),
+        }
     }
 
-    fn estimate_bandwidth(&mut self, data: ArrayView2<A>) {
+    /// Estimates the global bandwidth with `params.bandwidth_estimator`, unless it is given.
+    /// In adaptive mode, returns the distance of every point to its `adaptive_k`-th nearest
+    /// neighbor if the estimator already compared all points with each other.
+    fn estimate_bandwidth(
+        &mut self,
+        data: ArrayView2<A>,
+        weights: Option<ArrayView1<A>>,
+    ) -> Result<Option<Vec<A>>> {
         match self.bandwidth {
             None => {
-                let quantile = A::from_f32(0.3).unwrap();
-                let data_rows = A::from(data.shape()[0]).unwrap();
-                let one = A::from_usize(1).unwrap();
-
-                let n_neighbors: usize = A::from(data_rows * quantile)
-                    .unwrap()
-                    .max(one)
-                    .to_usize()
-                    .unwrap();
-
-                let mut tree = KdTree::new(data.shape()[1]);
-                for (i, point) in data.axis_iter(Axis(0)).enumerate() {
-                    tree.add(RefArray(point.to_shared()), i).unwrap();
-                }
+                let (bandwidth, kth_distances) = self
+                    .params
+                    .bandwidth_estimator
+                    .estimate_sharing::<A, D>(data, weights, self.params.adaptive_k)?;
+                self.bandwidth = Some(bandwidth);
+                Ok(kth_distances)
+            }
+            _ => {
+                debug!("Skipping bandwidth estimation, because a bandwidth is already given.");
+                Ok(None)
+            }
+        }
+    }
 
-                let bandwidth: A = data
+    /// In adaptive mode, gives every point the distance to its `adaptive_k`-th nearest neighbor
+    /// as its bandwidth, counting every neighbor with its weight. These are the `kth_distances`
+    /// of the bandwidth estimation if it compared all points with each other, and otherwise
+    /// queried from `tree`, since a subsample or another estimator does not see the neighbors of
+    /// every point.
+    fn adapt_bandwidths(
+        &mut self,
+        data: ArrayView2<A>,
+        tree: &dyn NeighborIndex<A>,
+        weights: Option<ArrayView1<A>>,
+        kth_distances: Option<Vec<A>>,
+    ) -> Result<()> {
+        let bandwidth = self.bandwidth.unwrap();
+        let positive = |distance: A| {
+            if distance > A::zero() {
+                distance
+            } else {
+                bandwidth
+            }
+        };
+        self.point_bandwidths = match (self.params.adaptive_k, kth_distances) {
+            (None, _) => None,
+            (Some(_), Some(kth_distances)) => {
+                debug!("Reusing the nearest neighbors of the bandwidth estimation.");
+                Some(kth_distances.into_iter().map(positive).collect())
+            }
+            (Some(k), None) => {
+                let bandwidths: Vec<A> = data
                     .axis_iter(Axis(0))
                     .into_par_iter()
                     .map(|x| {
-                        let nearest = tree
-                            .nearest(
-                                x.as_slice().unwrap(),
-                                n_neighbors,
-                                &<D as DistanceMeasure<A>>::distance_slice,
-                            )
-                            .unwrap();
-                        nearest
-                            .into_iter()
-                            .map(|(dist, _)| dist)
-                            .fold(A::min_value(), A::max)
+                        // the nearest neighbor of every point is the point itself
+                        let needed = A::from_usize(k + 1).unwrap();
+                        let query = contiguous(&x);
+                        let mut n_nearest = k + 1;
+                        let kth_distance = loop {
+                            let nearest = tree.nearest(&query, n_nearest)?;
+                            let mut seen = A::zero();
+                            let reached = nearest.iter().find(|(_, i)| {
+                                seen = seen + weights.map_or(A::one(), |w| w[*i]);
+                                seen >= needed
+                            });
+                            match reached {
+                                Some((dist, _)) => break *dist,
+                                None if n_nearest >= tree.len() => {
+                                    break nearest.last().map_or(A::zero(), |(dist, _)| *dist)
+                                }
+                                None => n_nearest *= 2,
+                            }
+                        };
+                        Ok(positive(kth_distance))
                     })
-                    .sum();
-
-                self.tree = Some(Arc::new(tree));
-                self.bandwidth = Some(bandwidth / data_rows);
+                    .collect::<Result<_>>()?;
+                Some(Array1::from(bandwidths))
             }
-            _ => debug!("Skipping bandwidth estimation, because a bandwidth is already given."),
-        }
+        };
+        Ok(())
     }
 
+    /// Merges the converged means into modes. Means in the same cell of a grid with cells of
+    /// `tol * bandwidth`, or within `tol * bandwidth` of each other, directly or through other
+    /// means, are the same mode. Going through these modes by decreasing intensity, every mode
+    /// that is not absorbed yet absorbs the weaker modes within its bandwidth. Returns the modes
+    /// ordered by decreasing intensity, their intensities and, for every seed identifier, the
+    /// index of the mode it was merged into.
     fn collect_means(
         &mut self,
-        mut means: Vec<(Array1<A>, usize, usize, usize)>,
-    ) -> Vec<Array1<A>> {
-        means.sort_by(|(a, a_intensity, _, _), (b, b_intensity, _, _)| {
-            let intensity_cmp = a_intensity.cmp(b_intensity);
+        mut means: Vec<(Trajectory<A>, usize)>,
+    ) -> Result<CollectedMeans<A>> {
+        means.par_sort_by(|(a, a_identifier), (b, b_identifier)| {
+            let intensity_cmp = a
+                .intensity
+                .partial_cmp(&b.intensity)
+                .unwrap_or(Ordering::Equal);
             match &intensity_cmp {
-                Ordering::Equal => a.slice_cmp(b).reverse(),
+                Ordering::Equal => a
+                    .mean
+                    .slice_cmp(&b.mean)
+                    .reverse()
+                    .then(a_identifier.cmp(b_identifier)),
                 _ => intensity_cmp.reverse(),
             }
         });
 
-        means.dedup_by_key(|(x, _, _, _)| x.clone());
-
-        let tree = self.center_tree.as_mut().unwrap();
-        for (point, _, _, i) in means.iter() {
-            tree.add(RefArray(point.to_shared()), *i).unwrap();
+        // means in the same cell of a grid as fine as the stopping tolerance are the same mode
+        let cell_size = self.bandwidth.unwrap() * self.params.tol;
+        let keys: Vec<Vec<i64>> = means
+            .par_iter()
+            .map(|(trajectory, _)| {
+                trajectory
+                    .mean
+                    .iter()
+                    .map(|x| (*x / cell_size).floor().to_i64().unwrap_or(i64::MAX))
+                    .collect()
+            })
+            .collect();
+        let union_find = UnionFind::new(means.len());
+        let mut cells: HashMap<&[i64], usize> = HashMap::new();
+        let mut representatives = vec![];
+        for (rank, key) in keys.iter().enumerate() {
+            match cells.get(key.as_slice()) {
+                Some(first) => union_find.union(*first, rank),
+                None => {
+                    cells.insert(key, rank);
+                    representatives.push(rank);
+                }
+            }
         }
 
-        let mut unique: HashMap<usize, bool> =
-            HashMap::from_iter(means.iter().map(|(_, _, _, i)| (*i, true)));
-
-        let distance_fn = &<D as DistanceMeasure<A>>::distance_slice;
+        let views: Vec<ArrayView1<A>> = representatives
+            .iter()
+            .map(|rank| means[*rank].0.mean.view())
+            .collect();
+        let stacked = stack(Axis(0), &views)?;
+        let tree = self.new_index(stacked.view())?;
+        representatives.par_iter().try_for_each(|rank| {
+            let mean = contiguous(&means[*rank].0.mean);
+            for (_, neighbor) in tree.within(&mean, cell_size)? {
+                union_find.union(*rank, representatives[neighbor]);
+            }
+            Ok::<_, MeanShiftError>(())
+        })?;
+        let roots: Vec<usize> = (0..means.len())
+            .into_par_iter()
+            .map(|rank| union_find.find(rank))
+            .collect();
 
-        for (mean, _, _, i) in means.iter() {
-            // todo: parallelize
-            if unique[i] {
-                let neighbor_idxs = self
-                    .center_tree
-                    .as_ref()
-                    .unwrap()
-                    .within(
-                        mean.as_slice().unwrap(),
-                        self.bandwidth.expect(
-                            "You must estimate or give a bandwidth before starting the algorithm!",
-                        ),
-                        distance_fn,
-                    )
-                    .unwrap();
-                for (_, neighbor) in neighbor_idxs {
-                    match unique.get_mut(neighbor) {
-                        None => {}
-                        Some(val) => *val = false,
-                    }
+        // every set is rooted at its most intense mean, which is one of the representatives
+        let leaders: Vec<usize> = representatives
+            .iter()
+            .copied()
+            .filter(|rank| roots[*rank] == *rank)
+            .collect();
+        let reached: Vec<Vec<usize>> = leaders
+            .par_iter()
+            .map(|rank| {
+                let trajectory = &means[*rank].0;
+                let mut reached: Vec<usize> = tree
+                    .within(&contiguous(&trajectory.mean), trajectory.bandwidth)?
+                    .into_iter()
+                    .map(|(_, neighbor)| roots[representatives[neighbor]])
+                    .filter(|root| root > rank)
+                    .collect();
+                reached.sort_unstable();
+                reached.dedup();
+                Ok(reached)
+            })
+            .collect::<Result<_>>()?;
+        let mut owners: Vec<Option<usize>> = vec![None; means.len()];
+        for (leader, reached) in leaders.iter().zip(reached.iter()) {
+            if owners[*leader].is_none() {
+                owners[*leader] = Some(*leader);
+                for root in reached {
+                    owners[*root].get_or_insert(*leader);
                 }
-                *unique.get_mut(i).unwrap() = true;
             }
         }
 
-        means
+        let mut labels: HashMap<usize, usize> = HashMap::new();
+        let mut centers = vec![];
+        let mut intensities = vec![];
+        let mut bandwidths = vec![];
+        for leader in leaders.iter() {
+            if owners[*leader] == Some(*leader) {
+                let trajectory = &means[*leader].0;
+                labels.insert(*leader, centers.len());
+                centers.push(trajectory.mean.clone());
+                intensities.push(trajectory.points_within);
+                bandwidths.push(trajectory.bandwidth);
+            }
+        }
+
+        let seed_modes: HashMap<usize, usize> = means
+            .iter()
+            .zip(roots.iter())
+            .map(|((_, identifier), root)| (*identifier, labels[&owners[*root].unwrap()]))
+            .collect();
+
+        Ok((centers, intensities, bandwidths, seed_modes))
+    }
+
+    fn fit_centers(&mut self, cluster_centers: &[Array1<A>], bandwidths: Vec<A>) -> Result<()> {
+        let views: Vec<ArrayView1<A>> = cluster_centers.iter().map(|x| x.view()).collect();
+        let stacked = stack(Axis(0), &views)?;
+        let center_tree = self.new_index(stacked.clone())?;
+        self.cluster_centers = Some(match &self.whitening {
+            Some(whitening) => whitening.unwhiten(stacked.view()),
+            None => stacked,
+        });
+        self.center_tree = Some(center_tree);
+        self.center_bandwidths = bandwidths;
+        Ok(())
+    }
+
+    fn label_data(&self, data: ArrayView2<A>) -> Result<Vec<i32>> {
+        let center_tree = self.center_tree.as_ref().unwrap();
+        let bandwidths = &self.center_bandwidths;
+        let cluster_all = self.params.cluster_all;
+        data.axis_iter(Axis(0))
             .into_par_iter()
-            .filter_map(|(mean, _, _, identifier)| {
-                if unique[&identifier] {
-                    Some(mean)
-                } else {
-                    None
-                }
+            .map(|x| {
+                let nearest = center_tree.nearest(&contiguous(&x), 1)?;
+                Ok(match nearest[0] {
+                    (distance, label) if !cluster_all && distance > bandwidths[label] => {
+                        NOISE_LABEL
+                    }
+                    (_, label) => label as i32,
+                })
             })
             .collect()
     }
 
-    fn label_data(&mut self, data: ArrayView2<A>, cluster_centers: Vec<ArrayView1<A>>) -> Vec<i32> {
-        let labels: Vec<i32> = data
-            .axis_iter(Axis(0))
-            .into_par_iter()
-            .map(|x| closest_distance::<_, D>(x, cluster_centers.clone()))
-            .collect();
-        labels
+    /// Labels `data` with the closest of the centers found by the last call to `cluster`.
+    pub fn predict(&self, data: ArrayView2<A>) -> Result<Vec<i32>> {
+        match &self.pool {
+            Some(pool) => pool.install(|| self.predict_inner(data)),
+            None => self.predict_inner(data),
+        }
+    }
+
+    fn predict_inner(&self, data: ArrayView2<A>) -> Result<Vec<i32>> {
+        let cluster_centers = self
+            .cluster_centers
+            .as_ref()
+            .ok_or(MeanShiftError::NotFitted)?;
+        validate_data::<A, D>(data)?;
+        if data.shape()[1] != cluster_centers.shape()[1] {
+            return Err(MeanShiftError::DimensionMismatch {
+                expected: cluster_centers.shape()[1],
+                actual: data.shape()[1],
+            });
+        }
+        self.label_data(self.whiten(data).view())
     }
 
-    pub fn cluster(&mut self, dataset: ArrayView2<A>) -> Result<(Vec<i32>, Vec<Array1<A>>)> {
-        self.estimate_bandwidth(dataset);
-        self.build_center_tree(dataset);
+    /// Clusters `dataset`, starting the trajectories from the seeds chosen by `params.seeding`.
+    pub fn cluster(&mut self, dataset: ArrayView2<A>) -> Result<ClusteringResult<A>> {
+        self.cluster_deduplicated(dataset, None, None)
+    }
 
-        let shared_tree = self.tree.as_ref().unwrap();
-        let bandwidth = self.bandwidth.as_ref().unwrap();
+    /// Clusters `dataset` like `cluster`, but counts every row `weights[i]` times: in the mean
+    /// updates, in the bandwidth estimation, in `min_bin_freq` and in the ranking of the modes.
+    pub fn cluster_weighted(
+        &mut self,
+        dataset: ArrayView2<A>,
+        weights: ArrayView1<A>,
+    ) -> Result<ClusteringResult<A>> {
+        validate_weights(weights, dataset.shape()[0])?;
+        self.cluster_deduplicated(dataset, Some(weights), None)
+    }
 
-        let means: Vec<(Array1<A>, usize, usize)> = dataset
-            .axis_iter(Axis(0))
-            .into_par_iter()
-            .enumerate()
-            .map(|(i, _)| mean_shift_single::<_, D>(dataset, shared_tree.clone(), i, *bandwidth))
-            .filter(|(_, points_within_len, _)| points_within_len.gt(&0))
-            .collect();
+    /// Clusters `dataset`, starting one trajectory from every row of `seeds`.
+    /// Labels are still assigned to every row of `dataset`.
+    pub fn cluster_with_seeds(
+        &mut self,
+        dataset: ArrayView2<A>,
+        seeds: ArrayView2<A>,
+    ) -> Result<ClusteringResult<A>> {
+        validate_data::<A, D>(seeds)?;
+        if seeds.shape()[1] != dataset.shape()[1] {
+            return Err(MeanShiftError::DimensionMismatch {
+                expected: dataset.shape()[1],
+                actual: seeds.shape()[1],
+            });
+        }
+        self.cluster_deduplicated(dataset, None, Some(seeds))
+    }
+
+    /// Clusters the unique rows of `dataset` if `params.deduplication` is on, and all of them
+    /// otherwise.
+    fn cluster_deduplicated(
+        &mut self,
+        dataset: ArrayView2<A>,
+        weights: Option<ArrayView1<A>>,
+        seeds: Option<ArrayView2<A>>,
+    ) -> Result<ClusteringResult<A>> {
+        self.install(|this| {
+            validate_data::<A, D>(dataset)?;
+            match this.params.deduplication.compress(dataset, weights)? {
+                None => this.cluster_rows(dataset, weights, seeds),
+                Some(compressed) => {
+                    debug!(
+                        "Clustering {} unique of {} rows.",
+                        compressed.rows.shape()[0],
+                        dataset.shape()[0]
+                    );
+                    let result = this.cluster_rows(
+                        compressed.rows.view(),
+                        Some(compressed.weights.view()),
+                        seeds,
+                    );
+                    Ok(compressed.expand(result?))
+                }
+            }
+        })
+    }
+
+    fn cluster_rows(
+        &mut self,
+        dataset: ArrayView2<A>,
+        weights: Option<ArrayView1<A>>,
+        seeds: Option<ArrayView2<A>>,
+    ) -> Result<ClusteringResult<A>> {
+        let whitened = self.prepare(dataset, weights)?;
+        let kth_distances = self.estimate_bandwidth(whitened.view(), weights)?;
+        // the cells of a grid follow the bandwidth
+        let tree = self.index(whitened.view())?;
+        self.adapt_bandwidths(whitened.view(), tree.as_ref(), weights, kth_distances)?;
+        let seeds = match (&seeds, &self.params.variant) {
+            // every row moves in blurring mode
+            (_, Variant::Blurring) => CowArray::from(whitened.view()),
+            (Some(seeds), _) => self.whiten(seeds.view()),
+            (None, _) => self.params.seeding.seeds::<A, D>(
+                whitened.view(),
+                weights,
+                self.bandwidth.unwrap(),
+                self.params.min_bin_freq,
+            )?,
+        };
+        self.cluster_prepared(whitened.view(), tree.as_ref(), weights, seeds.view())
+    }
+
+    /// Validates the parameters and returns `dataset` in the coordinates of the bandwidth.
+    fn prepare<'a>(
+        &mut self,
+        dataset: ArrayView2<'a, A>,
+        weights: Option<ArrayView1<A>>,
+    ) -> Result<CowArray<'a, A, Ix2>> {
+        self.params.validate()?;
+        if let Some(bandwidth) = self.bandwidth {
+            validate_bandwidth(bandwidth)?;
+        }
+        self.whitening = self.params.anisotropy.whitening(dataset, weights)?;
+        if self.whitening.is_some() && self.bandwidth.is_none() {
+            // the bandwidth matrix already sets the scale
+            self.bandwidth = Some(A::one());
+        }
+        Ok(self.whiten(dataset))
+    }
 
-        let means: Vec<(Array1<A>, usize, usize, usize)> = means
+    fn context<'a>(
+        &'a self,
+        data: ArrayView2<'a, A>,
+        tree: &'a dyn NeighborIndex<A>,
+        weights: Option<ArrayView1<'a, A>>,
+    ) -> ShiftContext<'a, A, K> {
+        let bandwidth = self.bandwidth.unwrap();
+        let truncation = self.kernel.truncation();
+        let (radius, wide_points) = match &self.point_bandwidths {
+            Some(b) => split_wide_points(b.view(), truncation),
+            None => (bandwidth * truncation, vec![]),
+        };
+        ShiftContext {
+            data,
+            tree,
+            bandwidth,
+            radius,
+            point_bandwidths: self.point_bandwidths.as_ref(),
+            wide_points,
+            weights,
+            kernel: &self.kernel,
+            params: &self.params,
+            basins: None,
+        }
+    }
+
+    /// Blurring mean shift: moves every row to the mean of its neighbors among the moved rows
+    /// and rebuilds the index, until no row moves more than `tol * bandwidth`.
+    fn blur(
+        &self,
+        dataset: ArrayView2<A>,
+        tree: &dyn NeighborIndex<A>,
+        weights: Option<ArrayView1<A>>,
+    ) -> Result<Vec<Trajectory<A>>> {
+        let stop_threshold = self.bandwidth.unwrap() * self.params.tol;
+        let distance_fn = &<D as DistanceMeasure<A>>::distance_slice;
+        let mut points = dataset.to_owned();
+        let mut iterations = 0;
+        loop {
+            let steps: Vec<Step<A>> = {
+                let rebuilt;
+                let tree = match iterations {
+                    0 => tree,
+                    _ => {
+                        rebuilt = self.new_index(points.view())?;
+                        rebuilt.as_ref()
+                    }
+                };
+                let context = self.context(points.view(), tree, weights.as_ref().map(|w| w.view()));
+                points
+                    .axis_iter(Axis(0))
+                    .into_par_iter()
+                    .map(|point| shift::<_, D, _>(&context, &point.to_owned()))
+                    .collect::<Result<_>>()?
+            };
+            let moved = steps
+                .par_iter()
+                .zip(points.axis_iter(Axis(0)).into_par_iter())
+                .map(|(step, point)| distance_fn(&contiguous(&step.mean), &contiguous(&point)))
+                .reduce(A::zero, A::max);
+
+            if moved < stop_threshold || iterations >= self.params.max_iter {
+                return Ok(steps
+                    .into_iter()
+                    .map(|step| Trajectory {
+                        mean: step.mean,
+                        points_within: step.points_within,
+                        intensity: step.intensity,
+                        iterations,
+                        bandwidth: step.bandwidth,
+                    })
+                    .collect());
+            }
+            let views: Vec<ArrayView1<A>> = steps.iter().map(|step| step.mean.view()).collect();
+            points = stack(Axis(0), &views)?;
+            iterations += 1;
+        }
+    }
+
+    fn cluster_prepared(
+        &mut self,
+        dataset: ArrayView2<A>,
+        tree: &dyn NeighborIndex<A>,
+        weights: Option<ArrayView1<A>>,
+        seeds: ArrayView2<A>,
+    ) -> Result<ClusteringResult<A>> {
+        let bandwidth = self.bandwidth.unwrap();
+        let max_iter = self.params.max_iter;
+        let min_bin_freq = self.params.min_bin_freq;
+        let min_intensity = A::from_usize(min_bin_freq).unwrap();
+
+        let trajectories: Vec<Trajectory<A>> = match self.params.variant {
+            Variant::Standard => {
+                let context =
+                    self.context(dataset.view(), tree, weights.as_ref().map(|w| w.view()));
+                match self.params.basin_radius {
+                    None => seeds
+                        .axis_iter(Axis(0))
+                        .into_par_iter()
+                        .map(|seed| {
+                            mean_shift_single::<_, D, _>(&context, seed).map(|c| c.trajectory)
+                        })
+                        .collect::<Result<_>>()?,
+                    Some(radius) => climb_with_basins::<_, D, _>(
+                        &context,
+                        seeds,
+                        Basins::new(dataset.shape()[0], radius * bandwidth),
+                    )?,
+                }
+            }
+            Variant::Blurring => self.blur(dataset.view(), tree, weights)?,
+        };
+
+        let neighbor_counts: Vec<usize> = trajectories.iter().map(|t| t.points_within).collect();
+        let iterations: Vec<usize> = trajectories.iter().map(|t| t.iterations).collect();
+        let hit_max_iter: Vec<bool> = iterations.iter().map(|i| *i >= max_iter).collect();
+
+        let means: Vec<(Trajectory<A>, usize)> = trajectories
             .into_iter()
             .enumerate()
-            .map(|(i, (means, points_within_len, iterations))| {
-                (means, points_within_len, iterations, i)
-            })
+            .filter(|(_, t)| t.intensity >= min_intensity)
+            .map(|(i, t)| (t, i))
             .collect();
 
-        let cluster_centers = self.collect_means(means);
-        Ok((
-            self.label_data(dataset, cluster_centers.iter().map(|x| x.view()).collect()),
-            cluster_centers,
-        ))
+        if means.is_empty() {
+            return Err(MeanShiftError::NoModeFound { min_bin_freq });
+        }
+
+        let (cluster_centers, support, bandwidths, seed_modes) = self.collect_means(means)?;
+        self.fit_centers(&cluster_centers, bandwidths)?;
+
+        Ok(ClusteringResult {
+            labels: self.label_data(dataset)?,
+            cluster_centers: self.cluster_centers.clone().unwrap(),
+            bandwidth,
+            bandwidth_matrix: self.whitening.as_ref().map(|w| w.matrix()),
+            point_bandwidths: self.point_bandwidths.as_ref().map(|b| b.to_vec()),
+            iterations,
+            neighbor_counts,
+            hit_max_iter,
+            seed_modes: (0..seeds.shape()[0])
+                .map(|i| seed_modes.get(&i).copied())
+                .collect(),
+            support,
+            index_backend: tree.backend(),
+        })
     }
 }
 
-pub fn mean_shift_single<A: LibData, D: DistanceMeasure<A>>(
-    data: ArrayView2<A>,
-    tree: Arc<KdTree<A, usize, RefArray<A>>>,
-    seed: usize,
-    bandwidth: A,
-) -> (Array1<A>, usize, usize) {
-    let stop_threshold = bandwidth.mul(A::from_f32(1e-3).unwrap());
-    let max_iter = 300;
+/// Splits off the `sqrt(n)` points with the largest bandwidths, which every mean update
+/// compares with directly, so that a few isolated points with huge bandwidths do not make every
+/// neighbor query return all points. Returns the largest kernel radius of the other points and
+/// the split off points.
+fn split_wide_points<A: LibData>(
+    point_bandwidths: ArrayView1<A>,
+    truncation: A,
+) -> (A, Vec<usize>) {
+    let n_points = point_bandwidths.len();
+    let n_wide = ((n_points as f64).sqrt() as usize).min(n_points.saturating_sub(1));
+    let mut order: Vec<usize> = (0..n_points).collect();
+    order.sort_by(|a, b| {
+        point_bandwidths[*b]
+            .partial_cmp(&point_bandwidths[*a])
+            .unwrap_or(Ordering::Equal)
+    });
+    let radius = match order.get(n_wide) {
+        Some(i) => point_bandwidths[*i] * truncation,
+        None => return (A::zero(), vec![]),
+    };
+    let wide = order
+        .into_iter()
+        .take(n_wide)
+        .filter(|i| point_bandwidths[*i] * truncation > radius)
+        .collect();
+    (radius, wide)
+}
 
-    let mut my_mean = data.index_axis(Axis(0), seed).to_owned();
+/// Climbs from every seed in rounds that double in size, taking the basins of the earlier
+/// rounds as a shortcut.
+fn climb_with_basins<A: LibData, D: DistanceMeasure<A>, K: Kernel>(
+    context: &ShiftContext<A, K>,
+    seeds: ArrayView2<A>,
+    mut basins: Basins<A>,
+) -> Result<Vec<Trajectory<A>>> {
+    let n_seeds = seeds.shape()[0];
+    let mut trajectories = Vec::with_capacity(n_seeds);
+    let mut start = 0;
+    while start < n_seeds {
+        let end = (start + (start + 1).min(MAX_BASIN_ROUND)).min(n_seeds);
+        let climbs: Vec<Climb<A>> = {
+            let context = context.with_basins(&basins);
+            seeds
+                .slice(s![start..end, ..])
+                .axis_iter(Axis(0))
+                .into_par_iter()
+                .map(|seed| mean_shift_single::<_, D, _>(&context, seed))
+                .collect::<Result<_>>()?
+        };
+        // in the order of the seeds, whatever the order in which they converged
+        for climb in climbs {
+            basins.record(&climb);
+            trajectories.push(climb.trajectory);
+        }
+        start = end;
+    }
+    Ok(trajectories)
+}
+
+/// Climbs from `seed` to a mode of the kernel density estimate of `context.data`. In adaptive
+/// mode every point `i` contributes with its own bandwidth `h_i` and is weighted by
+/// `(h / h_i)^(d + 2)`, which gives the sample point estimator. With `context.basins`, the
+/// climb stops as soon as it comes close to a point in the basin of a converged trajectory.
+pub fn mean_shift_single<A: LibData, D: DistanceMeasure<A>, K: Kernel>(
+    context: &ShiftContext<A, K>,
+    seed: ArrayView1<A>,
+) -> Result<Climb<A>> {
+    let stop_threshold = context.bandwidth.mul(context.params.tol);
+    let max_iter = context.params.max_iter;
+
+    let mut my_mean = Array1::from(seed.to_vec());
     let mut iterations: usize = 0;
-    let mut points_within_len: usize = 0;
+    let mut points_within_len: usize;
+    let mut intensity;
+    let mut local_bandwidth = context.bandwidth;
 
     let distance_fn = &<D as DistanceMeasure<A>>::distance_slice;
-    let mean_fn = &<D as DistanceMeasure<A>>::mean_1d;
-    let zero = Array1::zeros([my_mean.shape()[0]]);
+    let mut visited = vec![];
 
     loop {
-        let within_result = tree.within(my_mean.as_slice().unwrap(), bandwidth, &distance_fn);
-        let neighbor_ids: Vec<usize> = match within_result {
-            Ok(neighbors) => neighbors.into_iter().map(|(_, x)| *x).collect(),
-            Err(_) => break,
-        };
+        let step = shift::<_, D, _>(context, &my_mean)?;
+        if let Some(basins) = context.basins {
+            visited.extend_from_slice(&step.nearby);
+            if let Some((id, trajectory)) = basins.lookup(&step.nearby) {
+                return Ok(Climb {
+                    trajectory: Trajectory {
+                        iterations,
+                        ..trajectory.clone()
+                    },
+                    visited,
+                    basin: Some(id),
+                    converged: false,
+                });
+            }
+        }
+        points_within_len = step.points_within;
+        intensity = step.intensity;
+        if points_within_len > 0 {
+            local_bandwidth = step.bandwidth;
+        }
 
-        let points_within: Vec<ArrayView1<A>> = neighbor_ids
-            .into_iter()
-            .map(|i| data.index_axis(Axis(0), i))
-            .collect();
-        points_within_len = points_within.len();
         let my_old_mean = my_mean;
-        my_mean = mean_fn(points_within).unwrap_or_else(|_| zero.clone());
+        my_mean = step.mean;
 
         if points_within_len == 0
-            || distance_fn(my_mean.as_slice().unwrap(), my_old_mean.as_slice().unwrap())
-                < stop_threshold
+            || distance_fn(&contiguous(&my_mean), &contiguous(&my_old_mean)) < stop_threshold
             || iterations >= max_iter
         {
             break;
@@ -232,28 +933,107 @@ pub fn mean_shift_single<A: LibData, D: DistanceMeasure<A>>(
         iterations += 1;
     }
 
-    (my_mean, points_within_len, iterations)
+    let trajectory = Trajectory {
+        mean: my_mean,
+        points_within: points_within_len,
+        intensity,
+        iterations,
+        bandwidth: local_bandwidth,
+    };
+    Ok(Climb {
+        trajectory,
+        visited,
+        basin: None,
+        converged: iterations < max_iter && points_within_len > 0,
+    })
 }
 
-pub fn closest_distance<A: LibData, D: DistanceMeasure<A>>(
-    data_point: ArrayView1<A>,
-    cluster_centers: Vec<ArrayView1<A>>,
-) -> i32 {
-    let distance_fn = &<D as DistanceMeasure<A>>::distance_slice;
+/// Moves `mean` to the kernel-weighted mean of its neighbors in `context.data`, or keeps it if
+/// that mean cannot be computed.
+fn shift<A: LibData, D: DistanceMeasure<A>, K: Kernel>(
+    context: &ShiftContext<A, K>,
+    mean: &Array1<A>,
+) -> Result<Step<A>> {
+    let ShiftContext {
+        data,
+        tree,
+        bandwidth,
+        radius,
+        point_bandwidths,
+        weights,
+        kernel,
+        basins,
+        ..
+    } = *context;
+    let exponent = data.shape()[1] as i32 + 2;
 
-    cluster_centers
+    let query = contiguous(mean);
+    let mut neighbors = tree.within(&query, radius)?;
+    if let Some(b) = point_bandwidths {
+        // the index only answers for the points that do not reach beyond `radius`
+        let truncation = kernel.truncation::<A>();
+        neighbors.retain(|(_, i)| b[*i] * truncation <= radius);
+        for i in context.wide_points.iter() {
+            let distance = D::distance_slice(&query, &contiguous(&data.row(*i)));
+            if distance <= b[*i] * truncation {
+                neighbors.push((distance, *i));
+            }
+        }
+    }
+    let nearby: Vec<usize> = match basins {
+        Some(basins) => neighbors
+            .iter()
+            .filter(|(d, _)| *d <= basins.radius)
+            .map(|(_, i)| *i)
+            .collect(),
+        None => vec![],
+    };
+    let weighted: Vec<(usize, A)> = neighbors
         .into_iter()
-        .map(|center| distance_fn(data_point.as_slice().unwrap(), center.as_slice().unwrap()))
-        .enumerate()
-        .reduce(
-            |(min_i, min), (i, x)| {
-                if x < min {
-                    (i, x)
-                } else {
-                    (min_i, min)
-                }
-            },
-        )
-        .unwrap()
-        .0 as i32
+        .map(|(d, i)| match point_bandwidths {
+            Some(b) => (
+                i,
+                kernel.weight(d / b[i]) * (bandwidth / b[i]).powi(exponent),
+            ),
+            None => (i, kernel.weight(d / bandwidth)),
+        })
+        .map(|(i, weight)| match weights {
+            Some(w) => (i, weight * w[i]),
+            None => (i, weight),
+        })
+        .filter(|(_, weight)| *weight > A::zero())
+        .collect();
+
+    let points_within = weighted.len();
+    let intensity = match weights {
+        Some(w) => weighted.iter().map(|(i, _)| w[*i]).sum(),
+        None => A::from_usize(points_within).unwrap(),
+    };
+    let local_bandwidth = match point_bandwidths {
+        Some(b) if points_within > 0 => {
+            weighted.iter().map(|(i, _)| b[*i]).sum::<A>() / A::from_usize(points_within).unwrap()
+        }
+        _ => bandwidth,
+    };
+
+    let shifted = match points_within {
+        // there is nothing to average
+        0 => mean.clone(),
+        _ => {
+            let (points, kernel_weights): (Vec<ArrayView1<A>>, Vec<A>) = weighted
+                .into_iter()
+                .map(|(i, weight)| (data.index_axis(Axis(0), i), weight))
+                .unzip();
+            <D as DistanceMeasure<A>>::weighted_mean_1d(points, Array1::from(kernel_weights).view())
+                .unwrap_or_else(|_| mean.clone())
+        }
+    };
+
+    Ok(Step {
+        mean: shifted,
+        points_within,
+        intensity,
+        bandwidth: local_bandwidth,
+        nearby,
+    })
 }
@@ -1,8 +1,18 @@
+use crate::bandwidth::{estimate_bandwidth, Anisotropy, BandwidthEstimator, DimensionEstimator};
+use crate::deduplication::Deduplication;
 use crate::distance_measure::euclidean::Euclidean;
-use crate::distance_measure::DTW;
-use crate::parallel::MeanShift;
-use crate::test_utils::{close_l1, read_data};
-use ndarray::{arr2, Array2};
+use crate::distance_measure::{Chebyshev, DTW};
+use crate::error::MeanShiftError;
+use crate::kernel::{Epanechnikov, Gaussian};
+use crate::neighbors::{HnswParams, IndexBackend};
+use crate::parallel::{split_wide_points, MeanShift, NOISE_LABEL};
+use crate::params::{MeanShiftParams, ParameterError, Variant};
+use crate::seeding::Seeding;
+use crate::test_utils::{circle_blobs, close_l1, read_data};
+use ndarray::{arr1, arr2, s, Array1, Array2, Axis, ShapeBuilder};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::Arc;
 
 // todo: compare Arc<Vec<ArrayView1<f64>>> vs Vec<ArcArray1<f64>>
 
@@ -13,14 +23,14 @@ fn test_parallel_meanshift() {
     let mut mean_shift = MeanShift::<f64, Euclidean>::default();
 
     let dataset = read_data("data/test.csv");
-    let (labels, centers) = mean_shift.cluster(dataset.view()).unwrap();
+    let result = mean_shift.cluster(dataset.view()).unwrap();
 
-    assert_eq!(100, labels.len());
-    assert_eq!(0, labels.into_iter().sum());
+    assert_eq!(100, result.labels.len());
+    assert_eq!(0, result.labels.into_iter().sum());
 
-    close_l1(expects[[0, 0]], centers[0][0], 0.01);
-    close_l1(expects[[0, 1]], centers[0][1], 0.01);
-    close_l1(expects[[0, 2]], centers[0][2], 0.01);
+    close_l1(expects[[0, 0]], result.cluster_centers[[0, 0]], 0.01);
+    close_l1(expects[[0, 1]], result.cluster_centers[[0, 1]], 0.01);
+    close_l1(expects[[0, 2]], result.cluster_centers[[0, 2]], 0.01);
 }
 
 #[test]
@@ -28,5 +38,857 @@ fn test_parallel_meanshift_dtw_runs_without_errors() {
     let mut mean_shift = MeanShift::<f64, DTW>::default();
 
     let dataset = read_data("data/test.csv");
-    let (_labels, _centers) = mean_shift.cluster(dataset.view()).unwrap();
+    let _result = mean_shift.cluster(dataset.view()).unwrap();
+}
+
+#[test]
+fn test_parallel_meanshift_dtw_isolated_seed() {
+    let dataset = read_data::<f64>("data/test.csv");
+    let seeds = arr2(&[[0.5, 0.5, 0.5], [100.0, 100.0, 100.0]]);
+
+    let mut mean_shift = MeanShift::new(DTW, Some(1.0));
+    let result = mean_shift
+        .cluster_with_seeds(dataset.view(), seeds.view())
+        .unwrap();
+    assert_eq!(0, result.neighbor_counts[1]);
+    assert_eq!(None, result.seed_modes[1]);
+    assert!(result.seed_modes[0].is_some());
+}
+
+#[test]
+fn test_parallel_meanshift_gaussian_kernel() {
+    let expects: Array2<f64> = arr2(&[[0.5185592, 0.43546146, 0.5697923]]);
+
+    let mut mean_shift = MeanShift::new(Euclidean, None).with_kernel(Gaussian::default());
+
+    let dataset = read_data("data/test.csv");
+    let result = mean_shift.cluster(dataset.view()).unwrap();
+
+    assert_eq!(100, result.labels.len());
+    assert_eq!(1, result.n_clusters());
+    close_l1(expects[[0, 0]], result.cluster_centers[[0, 0]], 0.1);
+    close_l1(expects[[0, 1]], result.cluster_centers[[0, 1]], 0.1);
+    close_l1(expects[[0, 2]], result.cluster_centers[[0, 2]], 0.1);
+}
+
+#[test]
+fn test_parallel_meanshift_compact_kernel_separates_clusters() {
+    let dataset = arr2(&[
+        [0.0, 0.0],
+        [0.1, 0.0],
+        [0.0, 0.1],
+        [5.0, 5.0],
+        [5.1, 5.0],
+        [5.0, 5.1],
+    ]);
+
+    let mut mean_shift = MeanShift::new(Euclidean, Some(1.0)).with_kernel(Epanechnikov);
+    let result = mean_shift.cluster(dataset.view()).unwrap();
+    let labels = result.labels;
+
+    assert_eq!(2, result.cluster_centers.shape()[0]);
+    assert_eq!(labels[0], labels[1]);
+    assert_eq!(labels[3], labels[5]);
+    assert_ne!(labels[0], labels[3]);
+}
+
+#[test]
+fn test_parallel_meanshift_rejects_invalid_params() {
+    let mut mean_shift =
+        MeanShift::<f64, Euclidean>::default().with_params(MeanShiftParams::default().max_iter(0));
+
+    let dataset = read_data("data/test.csv");
+    let error = mean_shift.cluster(dataset.view()).unwrap_err();
+
+    assert_eq!(
+        error,
+        MeanShiftError::InvalidParameter(ParameterError::MaxIterZero)
+    );
+}
+
+#[test]
+fn test_parallel_meanshift_min_bin_freq() {
+    let dataset: Array2<f64> = read_data("data/test.csv");
+
+    let mut mean_shift = MeanShift::<f64, Euclidean>::default()
+        .with_params(MeanShiftParams::default().min_bin_freq(dataset.shape()[0] + 1));
+
+    assert_eq!(
+        mean_shift.cluster(dataset.view()).unwrap_err(),
+        MeanShiftError::NoModeFound {
+            min_bin_freq: dataset.shape()[0] + 1
+        }
+    );
+}
+
+#[test]
+fn test_parallel_meanshift_seeding_strategies() {
+    let dataset = circle_blobs(&[[0.0, 0.0], [10.0, 10.0], [0.0, 10.0]], 10, 0.1);
+
+    for seeding in [
+        Seeding::Bin,
+        Seeding::Random {
+            n_seeds: 12,
+            seed: 0,
+        },
+        Seeding::FarthestPoint { n_seeds: 3 },
+    ] {
+        let mut mean_shift = MeanShift::<f64, Euclidean>::default()
+            .with_params(MeanShiftParams::default().seeding(seeding));
+        let result = mean_shift.cluster(dataset.view()).unwrap();
+
+        assert_eq!(30, result.labels.len());
+        assert_eq!(3, result.n_clusters());
+        for blob in result.labels.chunks(10) {
+            assert!(blob.iter().all(|label| *label == blob[0]));
+        }
+    }
+}
+
+#[test]
+fn test_parallel_meanshift_explicit_seeds() {
+    let dataset = read_data("data/test.csv");
+    let seeds = arr2(&[[0.5, 0.5, 0.5]]);
+
+    let mut mean_shift = MeanShift::<f64, Euclidean>::default();
+    let result = mean_shift
+        .cluster_with_seeds(dataset.view(), seeds.view())
+        .unwrap();
+
+    assert_eq!(100, result.labels.len());
+    assert_eq!(1, result.n_clusters());
+    assert!(mean_shift
+        .cluster_with_seeds(dataset.view(), arr2(&[[0.5, 0.5]]).view())
+        .is_err());
+}
+
+#[test]
+fn test_parallel_meanshift_noise_label() {
+    let mut dataset = circle_blobs(&[[0.0, 0.0], [10.0, 10.0]], 10, 0.1);
+    dataset[[19, 0]] = 50.0;
+    let seeds = arr2(&[[0.0, 0.0], [10.0, 10.0]]);
+
+    let mut mean_shift = MeanShift::<f64, Euclidean>::default();
+    let labels = mean_shift
+        .cluster_with_seeds(dataset.view(), seeds.view())
+        .unwrap()
+        .labels;
+    assert_ne!(NOISE_LABEL, labels[19]);
+
+    let mut mean_shift = MeanShift::<f64, Euclidean>::default()
+        .with_params(MeanShiftParams::default().cluster_all(false));
+    let labels = mean_shift
+        .cluster_with_seeds(dataset.view(), seeds.view())
+        .unwrap()
+        .labels;
+    assert_eq!(NOISE_LABEL, labels[19]);
+    assert!(labels[..19].iter().all(|label| *label != NOISE_LABEL));
+}
+
+#[test]
+fn test_parallel_meanshift_predict() {
+    let dataset = circle_blobs(&[[0.0, 0.0], [10.0, 10.0]], 10, 0.1);
+    let new_data = arr2(&[[0.05, 0.0], [9.9, 10.1], [50.0, 50.0]]);
+
+    let mut mean_shift = MeanShift::<f64, Euclidean>::default();
+    assert_eq!(
+        mean_shift.predict(new_data.view()).unwrap_err(),
+        MeanShiftError::NotFitted
+    );
+
+    let result = mean_shift.cluster(dataset.view()).unwrap();
+    let labels = result.labels;
+    assert_eq!(
+        mean_shift.cluster_centers.as_ref().unwrap(),
+        result.cluster_centers
+    );
+
+    let predicted = mean_shift.predict(new_data.view()).unwrap();
+    assert_eq!(predicted[0], labels[0]);
+    assert_eq!(predicted[1], labels[10]);
+    assert_eq!(
+        mean_shift.predict(arr2(&[[0.0]]).view()).unwrap_err(),
+        MeanShiftError::DimensionMismatch {
+            expected: 2,
+            actual: 1
+        }
+    );
+
+    mean_shift.params.cluster_all = false;
+    assert_eq!(NOISE_LABEL, mean_shift.predict(new_data.view()).unwrap()[2]);
+}
+
+#[test]
+fn test_parallel_meanshift_result_statistics() {
+    let dataset = circle_blobs(&[[0.0, 0.0], [10.0, 10.0]], 10, 0.1);
+    let seeds = arr2(&[[0.0, 0.0], [0.05, 0.0], [10.0, 10.0], [50.0, 50.0]]);
+
+    let mut mean_shift = MeanShift::<f64, Euclidean>::default();
+    let result = mean_shift
+        .cluster_with_seeds(dataset.view(), seeds.view())
+        .unwrap();
+
+    assert_eq!(2, result.n_clusters());
+    assert_eq!(result.bandwidth, mean_shift.bandwidth.unwrap());
+    assert_eq!(4, result.iterations.len());
+    assert_eq!(0, result.n_unconverged());
+    assert_eq!(0, result.neighbor_counts[3]);
+    assert_eq!(result.seed_modes[0], result.seed_modes[1]);
+    assert_eq!(Some(result.labels[0] as usize), result.seed_modes[0]);
+    assert_eq!(Some(result.labels[10] as usize), result.seed_modes[2]);
+    assert_eq!(None, result.seed_modes[3]);
+    assert_eq!(vec![10, 10], result.support);
+}
+
+#[test]
+fn test_parallel_meanshift_reports_max_iter() {
+    let dataset = read_data("data/test.csv");
+
+    let mut mean_shift =
+        MeanShift::<f64, Euclidean>::default().with_params(MeanShiftParams::default().max_iter(1));
+    let result = mean_shift.cluster(dataset.view()).unwrap();
+
+    assert!(result.n_unconverged() > 0);
+    assert!(result.iterations.iter().all(|i| *i <= 1));
+}
+
+#[test]
+fn test_parallel_meanshift_own_thread_pools() {
+    let dataset = read_data("data/test.csv");
+
+    let mut single = MeanShift::<f64, Euclidean>::new_with_threads(Euclidean, None, 1).unwrap();
+    let mut multi = MeanShift::<f64, Euclidean>::new_with_threads(Euclidean, None, 3).unwrap();
+    let single_result = single.cluster(dataset.view()).unwrap();
+    let multi_result = multi.cluster(dataset.view()).unwrap();
+
+    assert_eq!(single_result.labels, multi_result.labels);
+    assert_eq!(single_result.cluster_centers, multi_result.cluster_centers);
+    assert_eq!(
+        single.predict(dataset.view()).unwrap(),
+        single_result.labels
+    );
+}
+
+#[test]
+fn test_parallel_meanshift_given_bandwidth_reuses_index() {
+    let dataset = circle_blobs(&[[0.0, 0.0], [3.0, 0.0]], 10, 0.1);
+
+    let mut mean_shift = MeanShift::new(Euclidean, Some(0.5));
+    assert_eq!(2, mean_shift.cluster(dataset.view()).unwrap().n_clusters());
+    // indexes of `cluster` only live for the call
+    assert!(mean_shift.tree.is_none());
+
+    mean_shift.build_index(dataset.view()).unwrap();
+    let tree = mean_shift.tree.clone().unwrap();
+    mean_shift.bandwidth = Some(5.0);
+    assert_eq!(1, mean_shift.cluster(dataset.view()).unwrap().n_clusters());
+    assert!(Arc::ptr_eq(&tree, mean_shift.tree.as_ref().unwrap()));
+
+    let other = circle_blobs(&[[0.0, 0.0], [30.0, 0.0]], 10, 0.1);
+    assert_eq!(2, mean_shift.cluster(other.view()).unwrap().n_clusters());
+    assert!(Arc::ptr_eq(&tree, mean_shift.tree.as_ref().unwrap()));
+}
+
+#[test]
+fn test_parallel_meanshift_does_not_reuse_index_of_dropped_rows() {
+    let mut mean_shift = MeanShift::new(Euclidean, Some(0.5));
+    let indexed = circle_blobs(&[[0.0, 0.0], [3.0, 0.0]], 10, 0.1);
+    let other = circle_blobs(&[[50.0, 50.0], [80.0, 50.0]], 10, 0.1);
+    mean_shift.build_index(indexed.view()).unwrap();
+    drop(indexed);
+
+    // may well be allocated where the indexed rows were
+    let dataset = other.clone();
+    let result = mean_shift.cluster(dataset.view()).unwrap();
+    let expected = MeanShift::new(Euclidean, Some(0.5))
+        .cluster(dataset.view())
+        .unwrap();
+    assert_eq!(expected.labels, result.labels);
+    assert_eq!(expected.cluster_centers, result.cluster_centers);
+}
+
+#[test]
+fn test_parallel_meanshift_borrowed_and_owned_rows_agree() {
+    let dataset = read_data::<f64>("data/test.csv");
+    let mut fortran = Array2::zeros(dataset.raw_dim().f());
+    fortran.assign(&dataset);
+
+    for backend in [
+        IndexBackend::KdTree,
+        IndexBackend::Grid,
+        IndexBackend::VpTree,
+    ] {
+        let params = MeanShiftParams::default().index_backend(backend);
+        let mut borrowed = MeanShift::<f64, Euclidean>::default().with_params(params.clone());
+        let expected = borrowed.cluster(dataset.view()).unwrap();
+        // rows that are not contiguous are copied into the index
+        let mut copied = MeanShift::<f64, Euclidean>::default().with_params(params.clone());
+        let result = copied.cluster(fortran.view()).unwrap();
+        assert_eq!(expected.labels, result.labels);
+        assert_eq!(expected.cluster_centers, result.cluster_centers);
+
+        let mut owned = MeanShift::<f64, Euclidean>::default().with_params(params);
+        owned.build_index(dataset.view()).unwrap();
+        // the grid waits for the estimated bandwidth
+        assert_eq!(backend == IndexBackend::Grid, owned.tree.is_none());
+        assert_eq!(
+            expected.labels,
+            owned.cluster(dataset.view()).unwrap().labels
+        );
+        let tree = owned.tree.clone().unwrap();
+        assert_eq!(backend, tree.backend());
+        owned.cluster(dataset.view()).unwrap();
+        assert!(Arc::ptr_eq(&tree, owned.tree.as_ref().unwrap()));
+    }
+}
+
+#[test]
+fn test_parallel_meanshift_rejects_invalid_data() {
+    let mut mean_shift = MeanShift::<f64, Euclidean>::default();
+
+    assert_eq!(
+        mean_shift
+            .cluster(Array2::zeros([0, 3]).view())
+            .unwrap_err(),
+        MeanShiftError::EmptyInput
+    );
+    assert_eq!(
+        mean_shift
+            .cluster(arr2(&[[0.0, 1.0], [f64::NAN, 0.0]]).view())
+            .unwrap_err(),
+        MeanShiftError::NonFiniteValue { row: 1, column: 0 }
+    );
+    // padding may end a time series, but not make up all of it
+    assert_eq!(
+        MeanShift::<f64, DTW>::default()
+            .cluster(arr2(&[[0.0, 1.0, f64::NAN], [f64::NAN, f64::NAN, f64::NAN]]).view())
+            .unwrap_err(),
+        MeanShiftError::NonFiniteValue { row: 1, column: 0 }
+    );
+}
+
+#[test]
+fn test_parallel_meanshift_accepts_any_layout() {
+    let dataset = circle_blobs(&[[0.0, 0.0], [3.0, 0.0]], 10, 0.1);
+    let mut expected = MeanShift::new(Euclidean, Some(0.5));
+    let expected = expected.cluster(dataset.view()).unwrap();
+
+    let mut fortran = Array2::zeros(dataset.raw_dim().f());
+    fortran.assign(&dataset);
+    let transposed = dataset.t().to_owned();
+    let mut wide = Array2::zeros([dataset.shape()[0], 4]);
+    wide.slice_mut(s![.., 1..3]).assign(&dataset);
+    let reversed = dataset.slice(s![.., ..;-1]).to_owned();
+
+    for view in [
+        fortran.view(),
+        transposed.t(),
+        wide.slice(s![.., 1..3]),
+        reversed.slice(s![.., ..;-1]),
+    ] {
+        let mut mean_shift = MeanShift::new(Euclidean, Some(0.5));
+        let result = mean_shift.cluster(view).unwrap();
+
+        assert_eq!(expected.labels, result.labels);
+        assert_eq!(expected.cluster_centers, result.cluster_centers);
+        assert_eq!(expected.labels, mean_shift.predict(view).unwrap());
+    }
+}
+
+#[test]
+fn test_parallel_meanshift_adaptive_bandwidth() {
+    let mut dataset = circle_blobs(&[[0.0, 0.0], [0.5, 0.0]], 10, 0.05);
+    let sparse = circle_blobs(&[[20.0, 0.0]], 10, 3.0);
+    dataset.append(Axis(0), sparse.view()).unwrap();
+
+    // a single bandwidth merges the dense blobs and splits up the sparse one
+    let mut fixed = MeanShift::<f64, Euclidean>::default();
+    let fixed_labels = fixed.cluster(dataset.view()).unwrap().labels;
+    assert_eq!(fixed_labels[0], fixed_labels[10]);
+
+    let mut adaptive = MeanShift::<f64, Euclidean>::default()
+        .with_params(MeanShiftParams::default().adaptive_k(5));
+    let result = adaptive.cluster(dataset.view()).unwrap();
+
+    assert_eq!(3, result.n_clusters());
+    assert_eq!(30, result.point_bandwidths.unwrap().len());
+    for blob in result.labels.chunks(10) {
+        assert!(blob.iter().all(|label| *label == blob[0]));
+    }
+}
+
+#[test]
+fn test_parallel_meanshift_adaptive_bandwidth_shares_estimation() {
+    let dataset = read_data::<f64>("data/test.csv");
+    let weights: Array1<f64> = (0..100).map(|i| (i % 3 + 1) as f64).collect();
+    let params = MeanShiftParams::default().adaptive_k(5);
+
+    for weights in [None, Some(weights.view())] {
+        // the estimation compares all rows with each other and yields the bandwidths of them
+        let mut shared = MeanShift::<f64, Euclidean>::default().with_params(params.clone());
+        let shared = match weights {
+            Some(weights) => shared.cluster_weighted(dataset.view(), weights),
+            None => shared.cluster(dataset.view()),
+        }
+        .unwrap();
+        let mut queried =
+            MeanShift::new(Euclidean, Some(shared.bandwidth)).with_params(params.clone());
+        let queried = match weights {
+            Some(weights) => queried.cluster_weighted(dataset.view(), weights),
+            None => queried.cluster(dataset.view()),
+        }
+        .unwrap();
+        assert_eq!(shared.point_bandwidths, queried.point_bandwidths);
+    }
+}
+
+#[test]
+fn test_parallel_meanshift_adaptive_bandwidth_with_outlier() {
+    let mut dataset = circle_blobs(&[[0.0, 0.0], [0.5, 0.0]], 10, 0.05);
+    let sparse = circle_blobs(&[[20.0, 0.0]], 10, 3.0);
+    dataset.append(Axis(0), sparse.view()).unwrap();
+    dataset
+        .append(Axis(0), arr2(&[[1000.0, 0.0]]).view())
+        .unwrap();
+
+    let mut adaptive = MeanShift::<f64, Euclidean>::default()
+        .with_params(MeanShiftParams::default().adaptive_k(5));
+    let result = adaptive.cluster(dataset.view()).unwrap();
+    for blob in result.labels[..30].chunks(10) {
+        assert!(blob.iter().all(|label| *label == blob[0]));
+    }
+
+    // the outlier does not widen the neighbor queries of all other points
+    let point_bandwidths = Array1::from(result.point_bandwidths.unwrap());
+    let (radius, wide_points) = split_wide_points(point_bandwidths.view(), 1.0);
+    assert!(wide_points.contains(&30));
+    assert!(radius < 10.0);
+}
+
+#[test]
+fn test_parallel_meanshift_anisotropic_bandwidth() {
+    // the second column is measured in a unit a thousand times smaller than the first one
+    let mut dataset = circle_blobs(
+        &[[0.0, 0.0], [10.0, 0.0], [0.0, 10.0], [10.0, 10.0]],
+        10,
+        1.0,
+    );
+    dataset.column_mut(1).mapv_inplace(|y| y * 1000.0);
+
+    let per_dimension = Anisotropy::PerDimension(arr1(&[3.0, 3000.0]));
+    let matrix = Anisotropy::Matrix(arr2(&[[9.0, 0.0], [0.0, 9e6]]));
+    let estimated = Anisotropy::Estimated(DimensionEstimator::Scott);
+    for anisotropy in [per_dimension, matrix, estimated] {
+        let mut mean_shift = MeanShift::<f64, Euclidean>::default()
+            .with_params(MeanShiftParams::default().anisotropy(anisotropy));
+        let result = mean_shift.cluster(dataset.view()).unwrap();
+
+        assert_eq!(4, result.n_clusters());
+        assert!(result.bandwidth_matrix.is_some());
+        for blob in result.labels.chunks(10) {
+            assert!(blob.iter().all(|label| *label == blob[0]));
+        }
+        // the centers are reported in the original coordinates
+        for center in result.cluster_centers.outer_iter() {
+            assert!(center[0].abs() < 0.01 || (center[0] - 10.0).abs() < 0.01);
+            assert!(center[1].abs() < 10.0 || (center[1] - 10000.0).abs() < 10.0);
+        }
+        assert_eq!(result.labels, mean_shift.predict(dataset.view()).unwrap());
+    }
+
+    // a single bandwidth cannot separate the blobs
+    let mut isotropic = MeanShift::<f64, Euclidean>::default();
+    let labels = isotropic.cluster(dataset.view()).unwrap().labels;
+    assert!(labels
+        .chunks(10)
+        .any(|blob| blob.iter().any(|label| *label != blob[0])));
+}
+
+#[test]
+fn test_parallel_meanshift_bandwidth_estimators() {
+    let dataset = circle_blobs(&[[0.0, 0.0], [10.0, 0.0]], 10, 1.0);
+    let estimators = [
+        BandwidthEstimator::KnnQuantile {
+            quantile: 0.3,
+            n_samples: Some(10),
+            seed: 3,
+        },
+        BandwidthEstimator::MedianPairwise {
+            n_samples: None,
+            seed: 0,
+        },
+        BandwidthEstimator::Silverman,
+    ];
+    let mut bandwidths = vec![];
+    for estimator in estimators {
+        let mut mean_shift = MeanShift::<f64, Euclidean>::default()
+            .with_params(MeanShiftParams::default().bandwidth_estimator(estimator.clone()));
+        let result = mean_shift.cluster(dataset.view()).unwrap();
+
+        assert_eq!(
+            estimator.estimate::<_, Euclidean>(dataset.view()).unwrap(),
+            result.bandwidth
+        );
+        bandwidths.push(result.bandwidth);
+    }
+    assert_eq!(
+        estimate_bandwidth::<_, Euclidean>(dataset.view(), 0.3, Some(10), 3).unwrap(),
+        bandwidths[0]
+    );
+    // most pairs of points lie in different blobs
+    assert!(bandwidths[1] > 8.0);
+}
+
+#[test]
+fn test_parallel_meanshift_weights_act_as_multiplicities() {
+    let unique = circle_blobs(&[[0.0, 0.0], [3.0, 0.0]], 10, 1.0);
+    let weights: Array1<f64> = (0..20).map(|i| if i < 10 { 3.0 } else { 1.0 }).collect();
+    let mut repeated = unique.clone();
+    for _ in 0..2 {
+        repeated
+            .append(Axis(0), unique.slice(s![..10, ..]))
+            .unwrap();
+    }
+
+    for seeding in [Seeding::All, Seeding::Bin] {
+        let params = MeanShiftParams::default().seeding(seeding).min_bin_freq(2);
+        let mut weighted = MeanShift::<f64, Euclidean>::default().with_params(params.clone());
+        let weighted = weighted
+            .cluster_weighted(unique.view(), weights.view())
+            .unwrap();
+        let mut plain = MeanShift::<f64, Euclidean>::default().with_params(params);
+        let plain = plain.cluster(repeated.view()).unwrap();
+
+        close_l1(plain.bandwidth, weighted.bandwidth, 1e-9);
+        assert_eq!(plain.n_clusters(), weighted.n_clusters());
+        assert_eq!(&plain.labels[..20], &weighted.labels[..]);
+        for (a, b) in plain
+            .cluster_centers
+            .iter()
+            .zip(weighted.cluster_centers.iter())
+        {
+            close_l1(*a, *b, 1e-6);
+        }
+    }
+}
+
+#[test]
+fn test_parallel_meanshift_strided_weights() {
+    let dataset = circle_blobs(&[[0.0, 0.0], [10.0, 0.0]], 10, 1.0);
+    let strided: Array1<f64> = (0..40).map(|i| (i % 3 + 1) as f64).collect();
+    let weights = strided.slice(s![..;2]);
+    assert!(weights.as_slice().is_none());
+
+    let mut mean_shift = MeanShift::<f64, Euclidean>::default();
+    let result = mean_shift
+        .cluster_weighted(dataset.view(), weights)
+        .unwrap();
+    let expected = MeanShift::<f64, Euclidean>::default()
+        .cluster_weighted(dataset.view(), weights.to_owned().view())
+        .unwrap();
+    assert_eq!(expected.labels, result.labels);
+    assert_eq!(expected.bandwidth, result.bandwidth);
+    assert_eq!(
+        BandwidthEstimator::default()
+            .estimate_weighted::<_, Euclidean>(dataset.view(), weights.to_owned().view())
+            .unwrap(),
+        BandwidthEstimator::default()
+            .estimate_weighted::<_, Euclidean>(dataset.view(), weights)
+            .unwrap()
+    );
+}
+
+#[test]
+fn test_parallel_meanshift_rejects_invalid_weights() {
+    let dataset = circle_blobs(&[[0.0, 0.0]], 4, 1.0);
+    let mut mean_shift = MeanShift::<f64, Euclidean>::default();
+    assert_eq!(
+        Err(MeanShiftError::WeightCountMismatch {
+            expected: 4,
+            actual: 3
+        }),
+        mean_shift
+            .cluster_weighted(dataset.view(), arr1(&[1.0, 1.0, 1.0]).view())
+            .map(|r| r.labels)
+    );
+    assert_eq!(
+        Err(MeanShiftError::InvalidParameter(
+            ParameterError::InvalidWeight(-1.0)
+        )),
+        mean_shift
+            .cluster_weighted(dataset.view(), arr1(&[1.0, -1.0, 1.0, 1.0]).view())
+            .map(|r| r.labels)
+    );
+    assert_eq!(
+        Err(MeanShiftError::InvalidParameter(
+            ParameterError::NonPositiveWeightSum
+        )),
+        mean_shift
+            .cluster_weighted(dataset.view(), Array1::zeros(4).view())
+            .map(|r| r.labels)
+    );
+}
+
+#[test]
+fn test_parallel_meanshift_dtw_weighted_runs_without_errors() {
+    let dataset = read_data("data/test.csv");
+    let weights: Array1<f64> = (0..100).map(|i| (i % 3 + 1) as f64).collect();
+    let mut mean_shift = MeanShift::<f64, DTW>::default();
+    let result = mean_shift
+        .cluster_weighted(dataset.view(), weights.view())
+        .unwrap();
+    assert_eq!(100, result.labels.len());
+}
+
+#[test]
+fn test_parallel_meanshift_deduplication() {
+    let unique = circle_blobs(&[[0.0, 0.0], [5.0, 0.0]], 8, 1.0);
+    let mut dataset = unique.clone();
+    for _ in 0..5 {
+        dataset.append(Axis(0), unique.view()).unwrap();
+    }
+
+    let mut plain = MeanShift::<f64, Euclidean>::default();
+    let plain = plain.cluster(dataset.view()).unwrap();
+    let mut exact = MeanShift::<f64, Euclidean>::default()
+        .with_params(MeanShiftParams::default().deduplication(Deduplication::Exact));
+    let exact = exact.cluster(dataset.view()).unwrap();
+
+    assert_eq!(96, exact.labels.len());
+    assert_eq!(16, exact.iterations.len());
+    assert_eq!(plain.labels, exact.labels);
+    close_l1(plain.bandwidth, exact.bandwidth, 1e-9);
+    for (a, b) in plain
+        .cluster_centers
+        .iter()
+        .zip(exact.cluster_centers.iter())
+    {
+        close_l1(*a, *b, 1e-6);
+    }
+
+    // a grid as coarse as the blobs collapses each of them into a single row
+    let mut grid = MeanShift::<f64, Euclidean>::new(Euclidean, Some(2.0)).with_params(
+        MeanShiftParams::default().deduplication(Deduplication::Grid { cell_size: 2.5 }),
+    );
+    let result = grid.cluster(dataset.view()).unwrap();
+    assert!(result.iterations.len() < 16);
+    assert_eq!(2, result.n_clusters());
+    assert_eq!(result.labels, grid.predict(dataset.view()).unwrap());
+}
+
+#[test]
+fn test_parallel_meanshift_blurring() {
+    let dataset = circle_blobs(&[[0.0, 0.0], [5.0, 0.0], [0.0, 5.0]], 12, 1.0);
+
+    let mut standard = MeanShift::<f64, Euclidean>::new(Euclidean, Some(2.0));
+    let standard = standard.cluster(dataset.view()).unwrap();
+    let mut blurring = MeanShift::<f64, Euclidean>::new(Euclidean, Some(2.0))
+        .with_params(MeanShiftParams::default().variant(Variant::Blurring));
+    let result = blurring.cluster(dataset.view()).unwrap();
+
+    assert_eq!(3, result.n_clusters());
+    assert_eq!(36, result.iterations.len());
+    assert_eq!(standard.labels, result.labels);
+    assert!(result.iterations[0] <= *standard.iterations.iter().max().unwrap());
+    assert_eq!(result.labels, blurring.predict(dataset.view()).unwrap());
+}
+
+#[test]
+fn test_parallel_meanshift_basin_shortcut() {
+    let dataset = read_data::<f64>("data/test.csv");
+
+    let mut plain = MeanShift::<f64, Euclidean>::default();
+    let plain = plain.cluster(dataset.view()).unwrap();
+    let mut shortcut = MeanShift::<f64, Euclidean>::default()
+        .with_params(MeanShiftParams::default().basin_radius(0.5));
+    let result = shortcut.cluster(dataset.view()).unwrap();
+
+    assert_eq!(plain.n_clusters(), result.n_clusters());
+    assert_eq!(plain.labels, result.labels);
+    assert!(result.iterations.iter().sum::<usize>() < plain.iterations.iter().sum::<usize>());
+
+    assert_eq!(
+        MeanShiftParams::<f64>::default()
+            .basin_radius(1.5)
+            .validate(),
+        Err(ParameterError::InvalidBasinRadius(1.5))
+    );
+}
+
+#[test]
+fn test_parallel_meanshift_basin_shortcut_with_several_modes() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let dataset = Array2::from_shape_fn((9 * 40, 2), |(i, j)| {
+        let blob = i / 40;
+        let center = [blob % 3, blob / 3][j] as f64 * 6.0;
+        center + rng.gen_range(-1.0..1.0)
+    });
+
+    let mut plain = MeanShift::new(Euclidean, Some(1.5));
+    let plain = plain.cluster(dataset.view()).unwrap();
+    let params = MeanShiftParams::default().basin_radius(0.5);
+    let mut single = MeanShift::new_with_threads(Euclidean, Some(1.5), 1)
+        .unwrap()
+        .with_params(params.clone());
+    let single = single.cluster(dataset.view()).unwrap();
+    let mut multi = MeanShift::new_with_threads(Euclidean, Some(1.5), 4)
+        .unwrap()
+        .with_params(params);
+    let multi = multi.cluster(dataset.view()).unwrap();
+
+    assert_eq!(9, plain.n_clusters());
+    assert_eq!(plain.labels, single.labels);
+    assert!(single.iterations.iter().sum::<usize>() < plain.iterations.iter().sum::<usize>());
+    // more seeds than climb in one round
+    assert_eq!(single.labels, multi.labels);
+    assert_eq!(single.iterations, multi.iterations);
+    assert_eq!(single.seed_modes, multi.seed_modes);
+}
+
+#[test]
+fn test_parallel_meanshift_merge_is_independent_of_threads() {
+    let mut rng = StdRng::seed_from_u64(5);
+    let n_blobs = 36;
+    let dataset = Array2::from_shape_fn((n_blobs * 20, 2), |(i, j)| {
+        let blob = i / 20;
+        let center = [blob % 6, blob / 6][j] as f64 * 6.0;
+        center + rng.gen_range(-1.0..1.0)
+    });
+    // the trajectories stop short of their modes in different cells of the merge grid, so the
+    // modes of a blob are only merged by the suppression within the bandwidth
+    let params = MeanShiftParams::default().seeding(Seeding::All).max_iter(2);
+
+    let mut single = MeanShift::new_with_threads(Euclidean, Some(1.5), 1)
+        .unwrap()
+        .with_kernel(Gaussian::default())
+        .with_params(params.clone());
+    let single = single.cluster(dataset.view()).unwrap();
+    let mut multi = MeanShift::new_with_threads(Euclidean, Some(1.5), 4)
+        .unwrap()
+        .with_kernel(Gaussian::default())
+        .with_params(params);
+    let multi = multi.cluster(dataset.view()).unwrap();
+
+    assert!(single.n_unconverged() > 0);
+    assert_eq!(n_blobs, single.n_clusters());
+    assert_eq!(single.labels, multi.labels);
+    assert_eq!(single.cluster_centers, multi.cluster_centers);
+    assert_eq!(single.seed_modes, multi.seed_modes);
+}
+
+#[test]
+fn test_parallel_meanshift_merge_does_not_chain_modes() {
+    let mut rng = StdRng::seed_from_u64(6);
+    let n_blobs = 10;
+    let dataset = Array2::from_shape_fn((n_blobs * 20, 2), |(i, j)| {
+        let center = if j == 0 { (i / 20) as f64 * 0.99 } else { 0.0 };
+        center + rng.gen_range(-0.01..0.01)
+    });
+
+    let mut mean_shift = MeanShift::new(Euclidean, Some(1.0)).with_kernel(Epanechnikov);
+    let result = mean_shift.cluster(dataset.view()).unwrap();
+
+    // every mode lies within the bandwidth of the next one, but a weaker mode is only absorbed
+    // by a stronger mode within the bandwidth of it, so the kept modes lie farther apart
+    assert_eq!(5, result.n_clusters());
+    let centers = &result.cluster_centers;
+    for i in 0..centers.nrows() {
+        for j in i + 1..centers.nrows() {
+            let difference = &centers.row(i) - &centers.row(j);
+            assert!(difference.dot(&difference).sqrt() > 1.0);
+        }
+    }
+}
+
+#[test]
+fn test_parallel_meanshift_index_backends() {
+    let dataset = read_data::<f64>("data/test.csv");
+
+    let mut grid = MeanShift::<f64, Euclidean>::default();
+    let grid_result = grid.cluster(dataset.view()).unwrap();
+    assert_eq!(IndexBackend::Grid, grid_result.index_backend);
+    for backend in [
+        IndexBackend::KdTree,
+        IndexBackend::VpTree,
+        IndexBackend::BruteForce,
+    ] {
+        let mut mean_shift = MeanShift::<f64, Euclidean>::default()
+            .with_params(MeanShiftParams::default().index_backend(backend));
+        let result = mean_shift.cluster(dataset.view()).unwrap();
+        assert_eq!(backend, result.index_backend);
+        assert_eq!(grid_result.labels, result.labels);
+    }
+
+    let mut dtw = MeanShift::<f64, DTW>::default()
+        .with_params(MeanShiftParams::default().index_backend(IndexBackend::KdTree));
+    assert_eq!(
+        dtw.cluster(dataset.view()).unwrap_err(),
+        MeanShiftError::InvalidParameter(ParameterError::UnsupportedIndexBackend {
+            backend: IndexBackend::KdTree,
+            distance_measure: "dtw"
+        })
+    );
+}
+
+#[test]
+fn test_parallel_meanshift_chebyshev_on_grid() {
+    let dataset = circle_blobs(&[[0.0, 0.0], [10.0, 10.0], [0.0, 10.0]], 10, 0.5);
+
+    let mut grid = MeanShift::<f64, Chebyshev>::new(Chebyshev, Some(1.0));
+    let result = grid.cluster(dataset.view()).unwrap();
+    assert_eq!(IndexBackend::Grid, result.index_backend);
+    assert_eq!(3, result.n_clusters());
+
+    let mut kd_tree = MeanShift::<f64, Chebyshev>::new(Chebyshev, Some(1.0))
+        .with_params(MeanShiftParams::default().index_backend(IndexBackend::KdTree));
+    assert_eq!(
+        result.labels,
+        kd_tree.cluster(dataset.view()).unwrap().labels
+    );
+}
+
+#[test]
+fn test_parallel_meanshift_hnsw_agrees_with_exact() {
+    let dataset = read_data::<f64>("data/test.csv");
+    let exact = MeanShift::<f64, Euclidean>::default()
+        .cluster(dataset.view())
+        .unwrap();
+
+    let params = HnswParams::default().m(8).ef_search(32);
+    let hnsw_params = MeanShiftParams::default().index_backend(IndexBackend::Hnsw(params));
+    let mut hnsw = MeanShift::<f64, Euclidean>::default().with_params(hnsw_params.clone());
+    let result = hnsw.cluster(dataset.view()).unwrap();
+    assert_eq!(IndexBackend::Hnsw(params), result.index_backend);
+    let agreement = result.agreement(&exact);
+    assert!(agreement.matched_fraction >= 0.95);
+    assert!(agreement.adjusted_rand_index >= 0.9);
+    // the graph is approximate under any distance measure
+    let mut dtw = MeanShift::<f64, DTW>::default().with_params(hnsw_params);
+    assert!(dtw.cluster(dataset.view()).is_ok());
+
+    let mut invalid = MeanShift::<f64, Euclidean>::default()
+        .with_params(MeanShiftParams::default().index_backend(IndexBackend::Hnsw(params.m(1))));
+    assert_eq!(
+        invalid.cluster(dataset.view()).unwrap_err(),
+        MeanShiftError::InvalidParameter(ParameterError::InvalidHnswParams(params.m(1)))
+    );
+}
+
+#[test]
+fn test_parallel_meanshift_blocked_in_high_dimensions() {
+    let blobs = circle_blobs(&[[0.0, 0.0], [6.0, 0.0]], 10, 1.0);
+    // the blobs embedded into 64 dimensions
+    let dataset = Array2::from_shape_fn([20, 64], |(i, j)| blobs[[i, j % 2]] / 32f64.sqrt());
+
+    let mut blocked = MeanShift::<f64, Euclidean>::new(Euclidean, Some(1.5));
+    let result = blocked.cluster(dataset.view()).unwrap();
+    assert_eq!(IndexBackend::Blocked, result.index_backend);
+
+    let mut kd_tree = MeanShift::<f64, Euclidean>::new(Euclidean, Some(1.5))
+        .with_params(MeanShiftParams::default().index_backend(IndexBackend::KdTree));
+    assert_eq!(2, result.n_clusters());
+    assert_eq!(
+        kd_tree.cluster(dataset.view()).unwrap().labels,
+        result.labels
+    );
 }
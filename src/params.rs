@@ -0,0 +1,290 @@
+use crate::bandwidth::{Anisotropy, BandwidthEstimator};
+use crate::deduplication::Deduplication;
+use crate::neighbors::{HnswParams, IndexBackend};
+use crate::seeding::Seeding;
+use crate::utils::LibData;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParameterError {
+    MaxIterZero,
+    InvalidTolerance(f64),
+    MinBinFreqZero,
+    InvalidBandwidth(f64),
+    NonPositiveWeightSum,
+    AdaptiveKZero,
+    InvalidQuantile(f64),
+    NotPositiveDefinite,
+    NSamplesZero,
+    InvalidWeight(f64),
+    InvalidCellSize(f64),
+    InvalidMaxDistance(f64),
+    InvalidBasinRadius(f64),
+    UnsupportedIndexBackend {
+        backend: IndexBackend,
+        distance_measure: &'static str,
+    },
+    InvalidHnswParams(HnswParams),
+}
+
+impl Display for ParameterError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MaxIterZero => write!(f, "max_iter must be at least 1"),
+            Self::InvalidTolerance(tol) => {
+                write!(f, "tol must be a positive finite number, got {}", tol)
+            }
+            Self::MinBinFreqZero => write!(f, "min_bin_freq must be at least 1"),
+            Self::InvalidBandwidth(bandwidth) => {
+                write!(
+                    f,
+                    "bandwidth must be a positive finite number, got {}",
+                    bandwidth
+                )
+            }
+            Self::NonPositiveWeightSum => write!(f, "weights must sum to a positive value"),
+            Self::AdaptiveKZero => write!(f, "adaptive_k must be at least 1"),
+            Self::InvalidQuantile(quantile) => {
+                write!(f, "quantile must lie in (0, 1], got {}", quantile)
+            }
+            Self::NotPositiveDefinite => {
+                write!(
+                    f,
+                    "the bandwidth matrix must be symmetric positive definite"
+                )
+            }
+            Self::NSamplesZero => write!(f, "n_samples must be at least 1"),
+            Self::InvalidWeight(weight) => write!(
+                f,
+                "weights must be non-negative finite numbers, got {}",
+                weight
+            ),
+            Self::InvalidCellSize(cell_size) => write!(
+                f,
+                "cell_size must be a positive finite number, got {}",
+                cell_size
+            ),
+            Self::InvalidMaxDistance(max_distance) => write!(
+                f,
+                "max_distance must be a positive finite number, got {}",
+                max_distance
+            ),
+            Self::InvalidBasinRadius(basin_radius) => {
+                write!(f, "basin_radius must lie in (0, 1], got {}", basin_radius)
+            }
+            Self::UnsupportedIndexBackend {
+                backend,
+                distance_measure,
+            } => write!(
+                f,
+                "the {} index does not find all neighbors under the {} distance",
+                backend, distance_measure
+            ),
+            Self::InvalidHnswParams(params) => write!(
+                f,
+                "hnsw needs m of at least 2 and ef_construction and ef_search of at least 1, got {:?}",
+                params
+            ),
+        }
+    }
+}
+
+impl Error for ParameterError {}
+
+/// Which mean shift algorithm `MeanShift` runs.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Variant {
+    /// Every seed climbs to a mode over the fixed dataset.
+    #[default]
+    Standard,
+    /// Every row is replaced by the mean of its neighbors in each iteration, so the whole
+    /// dataset contracts towards the modes. `seeding` and explicit seeds are ignored.
+    Blurring,
+}
+
+/// Parameters of the mean shift hill-climb and of everything around it.
+#[derive(Clone, Debug)]
+pub struct MeanShiftParams<A: LibData> {
+    /// Iterations after which a trajectory stops.
+    pub max_iter: usize,
+    /// A trajectory stops when the mean moves less than `tol * bandwidth`.
+    pub tol: A,
+    /// Seeds that end up with fewer neighbors, counted with their weights, are discarded.
+    pub min_bin_freq: usize,
+    /// Starting points of the trajectories.
+    pub seeding: Seeding,
+    /// If `false`, points farther than the bandwidth from every mode are labeled as noise
+    /// (`-1`).
+    pub cluster_all: bool,
+    /// Every point gets its own bandwidth: the distance to its `adaptive_k`-th nearest
+    /// neighbor, taken from the distances of the bandwidth estimation if it compares all points
+    /// with each other. Every mean update then queries the neighbors within the largest of these
+    /// bandwidths, apart from those of the `sqrt(n)` sparsest points, which it compares with
+    /// directly. Data whose density varies a lot beyond these points therefore costs up to `n`
+    /// distances per update.
+    pub adaptive_k: Option<usize>,
+    /// Lets the bandwidth differ across dimensions.
+    pub anisotropy: Anisotropy<A>,
+    /// Estimates the bandwidth if `MeanShift` is not given one.
+    pub bandwidth_estimator: BandwidthEstimator,
+    /// Collapses duplicate rows before clustering.
+    pub deduplication: Deduplication<A>,
+    /// Standard or blurring mean shift.
+    pub variant: Variant,
+    /// The points within `basin_radius * bandwidth` of a converged trajectory are assigned to
+    /// its mode, and trajectories that come that close to them stop early. The seeds climb in
+    /// rounds that only see the trajectories of earlier rounds, so that the result does not
+    /// depend on the number of threads.
+    pub basin_radius: Option<A>,
+    /// Neighbor index, by default the one the distance measure prefers.
+    pub index_backend: Option<IndexBackend>,
+}
+
+impl<A: LibData> Default for MeanShiftParams<A> {
+    fn default() -> Self {
+        Self {
+            max_iter: 300,
+            tol: A::from_f32(1e-3).unwrap(),
+            min_bin_freq: 1,
+            seeding: Seeding::All,
+            cluster_all: true,
+            adaptive_k: None,
+            anisotropy: Anisotropy::Isotropic,
+            bandwidth_estimator: BandwidthEstimator::default(),
+            deduplication: Deduplication::Off,
+            variant: Variant::Standard,
+            basin_radius: None,
+            index_backend: None,
+        }
+    }
+}
+
+impl<A: LibData> MeanShiftParams<A> {
+    pub fn max_iter(mut self, max_iter: usize) -> Self {
+        self.max_iter = max_iter;
+        self
+    }
+
+    pub fn tol(mut self, tol: A) -> Self {
+        self.tol = tol;
+        self
+    }
+
+    pub fn min_bin_freq(mut self, min_bin_freq: usize) -> Self {
+        self.min_bin_freq = min_bin_freq;
+        self
+    }
+
+    pub fn seeding(mut self, seeding: Seeding) -> Self {
+        self.seeding = seeding;
+        self
+    }
+
+    pub fn cluster_all(mut self, cluster_all: bool) -> Self {
+        self.cluster_all = cluster_all;
+        self
+    }
+
+    pub fn adaptive_k(mut self, adaptive_k: usize) -> Self {
+        self.adaptive_k = Some(adaptive_k);
+        self
+    }
+
+    pub fn anisotropy(mut self, anisotropy: Anisotropy<A>) -> Self {
+        self.anisotropy = anisotropy;
+        self
+    }
+
+    pub fn bandwidth_estimator(mut self, bandwidth_estimator: BandwidthEstimator) -> Self {
+        self.bandwidth_estimator = bandwidth_estimator;
+        self
+    }
+
+    pub fn deduplication(mut self, deduplication: Deduplication<A>) -> Self {
+        self.deduplication = deduplication;
+        self
+    }
+
+    pub fn variant(mut self, variant: Variant) -> Self {
+        self.variant = variant;
+        self
+    }
+
+    pub fn basin_radius(mut self, basin_radius: A) -> Self {
+        self.basin_radius = Some(basin_radius);
+        self
+    }
+
+    pub fn index_backend(mut self, index_backend: IndexBackend) -> Self {
+        self.index_backend = Some(index_backend);
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), ParameterError> {
+        if self.max_iter == 0 {
+            return Err(ParameterError::MaxIterZero);
+        }
+        if !self.tol.is_finite() || self.tol <= A::zero() {
+            return Err(ParameterError::InvalidTolerance(
+                self.tol.to_f64().unwrap_or(f64::NAN),
+            ));
+        }
+        if self.min_bin_freq == 0 {
+            return Err(ParameterError::MinBinFreqZero);
+        }
+        if self.adaptive_k == Some(0) {
+            return Err(ParameterError::AdaptiveKZero);
+        }
+        if let Some(basin_radius) = self.basin_radius {
+            if !(basin_radius > A::zero() && basin_radius <= A::one()) {
+                return Err(ParameterError::InvalidBasinRadius(
+                    basin_radius.to_f64().unwrap_or(f64::NAN),
+                ));
+            }
+        }
+        self.anisotropy.validate()?;
+        self.bandwidth_estimator.validate()?;
+        self.deduplication.validate()
+    }
+}
+
+pub(crate) fn validate_bandwidth<A: LibData>(bandwidth: A) -> Result<(), ParameterError> {
+    if !bandwidth.is_finite() || bandwidth <= A::zero() {
+        return Err(ParameterError::InvalidBandwidth(
+            bandwidth.to_f64().unwrap_or(f64::NAN),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_params_are_valid() {
+        assert!(MeanShiftParams::<f64>::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        let params = MeanShiftParams::<f64>::default();
+        assert_eq!(
+            params.clone().max_iter(0).validate(),
+            Err(ParameterError::MaxIterZero)
+        );
+        assert_eq!(
+            params.clone().tol(-1.0).validate(),
+            Err(ParameterError::InvalidTolerance(-1.0))
+        );
+        assert_eq!(
+            params.min_bin_freq(0).validate(),
+            Err(ParameterError::MinBinFreqZero)
+        );
+        assert_eq!(
+            validate_bandwidth(0.0),
+            Err(ParameterError::InvalidBandwidth(0.0))
+        );
+    }
+}
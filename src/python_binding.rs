@@ -1,7 +1,8 @@
-use crate::distance_measure::{Euclidean, Manhattan, DTW};
+use crate::distance_measure::{Chebyshev, Euclidean, Manhattan, DTW};
 use crate::DistanceMeasure;
 use crate::MeanShift;
 use numpy::{IntoPyArray, PyArray1, PyReadonlyArray2};
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 
 type LibDataType = f64;
@@ -10,32 +11,46 @@ type LibDataType = f64;
 fn meanshift_algorithm<'py>(
     py: Python<'py>,
     data: PyReadonlyArray2<'py, LibDataType>,
-    n_threads: usize,
+    n_threads: isize,
     bandwidth: Option<LibDataType>,
     distance_measure: String,
 ) -> PyResult<(Vec<&'py PyArray1<LibDataType>>, Vec<i32>)> {
     let data = data.as_array();
+    let n_threads = n_threads.max(0) as usize;
 
-    let (labels, cluster_centers) = match distance_measure.as_str() {
+    let result = match distance_measure.as_str() {
         <Euclidean as DistanceMeasure<LibDataType>>::NAME => {
-            MeanShift::new_with_threads(Euclidean, bandwidth, n_threads).cluster(data.view())
+            MeanShift::new_with_threads(Euclidean, bandwidth, n_threads)
+                .and_then(|mut mean_shift| mean_shift.cluster(data.view()))
         }
         <Manhattan as DistanceMeasure<LibDataType>>::NAME => {
-            MeanShift::new_with_threads(Manhattan, bandwidth, n_threads).cluster(data.view())
+            MeanShift::new_with_threads(Manhattan, bandwidth, n_threads)
+                .and_then(|mut mean_shift| mean_shift.cluster(data.view()))
+        }
+        <Chebyshev as DistanceMeasure<LibDataType>>::NAME => {
+            MeanShift::new_with_threads(Chebyshev, bandwidth, n_threads)
+                .and_then(|mut mean_shift| mean_shift.cluster(data.view()))
         }
         <DTW as DistanceMeasure<LibDataType>>::NAME => {
-            MeanShift::new_with_threads(DTW, bandwidth, n_threads).cluster(data.view())
+            MeanShift::new_with_threads(DTW, bandwidth, n_threads)
+                .and_then(|mut mean_shift| mean_shift.cluster(data.view()))
+        }
+        &_ => {
+            return Err(PyValueError::new_err(format!(
+                "Distance measure {} not known.",
+                distance_measure
+            )))
         }
-        &_ => panic!("Distance measure {} not known.", distance_measure),
     }
-    .expect("Clustering failed.");
+    .map_err(|error| PyValueError::new_err(error.to_string()))?;
 
-    let cluster_centers: Vec<&PyArray1<LibDataType>> = cluster_centers
-        .into_iter()
-        .map(|x| x.into_pyarray(py))
+    let cluster_centers: Vec<&PyArray1<LibDataType>> = result
+        .cluster_centers
+        .outer_iter()
+        .map(|x| x.to_owned().into_pyarray(py))
         .collect();
 
-    Ok((cluster_centers, labels))
+    Ok((cluster_centers, result.labels))
 }
 
 #[pymodule]
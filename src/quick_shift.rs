@@ -0,0 +1,199 @@
+use crate::bandwidth::BandwidthEstimator;
+use crate::distance_measure::DistanceMeasure;
+use crate::error::Result;
+use crate::kernel::{Flat, Kernel};
+use crate::neighbors::{compare_neighbors, IndexBackend};
+use crate::params::{validate_bandwidth, ParameterError};
+use crate::result::QuickShiftResult;
+use crate::utils::{contiguous, forest_roots, validate_data, LibData};
+use ndarray::{ArrayView2, Axis};
+use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+
+/// Quick shift, a mode-seeking clustering without a mean step.
+///
+/// Every point is linked to its nearest neighbor with a higher kernel density estimate, if
+/// that neighbor is at most `max_distance` away. The trees of the resulting forest are the
+/// clusters, rooted at their densest point. Cutting the links longer than a smaller threshold
+/// splits the trees, which gives a hierarchy of clusterings from a single run. Without
+/// `max_distance`, links may be up to three bandwidths long.
+#[derive(Default)]
+pub struct QuickShift<A: LibData, D: DistanceMeasure<A>, K: Kernel = Flat> {
+    pub bandwidth: Option<A>,
+    pub max_distance: Option<A>,
+    pub kernel: K,
+    distance_measure: PhantomData<D>,
+}
+
+impl<A: LibData, D: DistanceMeasure<A>> QuickShift<A, D> {
+    pub fn new(_distance_measure: D, bandwidth: Option<A>, max_distance: Option<A>) -> Self {
+        Self {
+            bandwidth,
+            max_distance,
+            kernel: Flat,
+            distance_measure: PhantomData,
+        }
+    }
+}
+
+impl<A: LibData, D: DistanceMeasure<A>, K: Kernel> QuickShift<A, D, K> {
+    pub fn with_kernel<K2: Kernel>(self, kernel: K2) -> QuickShift<A, D, K2> {
+        QuickShift {
+            bandwidth: self.bandwidth,
+            max_distance: self.max_distance,
+            kernel,
+            distance_measure: PhantomData,
+        }
+    }
+
+    pub fn cluster(&mut self, dataset: ArrayView2<A>) -> Result<QuickShiftResult<A>> {
+        validate_data::<A, D>(dataset)?;
+        let bandwidth = match self.bandwidth {
+            Some(bandwidth) => {
+                validate_bandwidth(bandwidth)?;
+                bandwidth
+            }
+            None => BandwidthEstimator::default().estimate::<A, D>(dataset)?,
+        };
+        self.bandwidth = Some(bandwidth);
+        let max_distance = self
+            .max_distance
+            .unwrap_or_else(|| bandwidth * A::from_f32(3.0).unwrap());
+        if validate_bandwidth(max_distance).is_err() {
+            return Err(ParameterError::InvalidMaxDistance(
+                max_distance.to_f64().unwrap_or(f64::NAN),
+            )
+            .into());
+        }
+
+        let tree = IndexBackend::select::<A, D>(None, dataset.shape()[1])?
+            .build::<A, D>(dataset, Some(bandwidth))?;
+        let radius = bandwidth * self.kernel.truncation();
+        let densities: Vec<A> = dataset
+            .axis_iter(Axis(0))
+            .into_par_iter()
+            .map(|point| {
+                Ok(tree
+                    .within(&contiguous(&point), radius)?
+                    .into_iter()
+                    .map(|(d, _)| self.kernel.weight(d / bandwidth))
+                    .sum())
+            })
+            .collect::<Result<_>>()?;
+
+        let links: Vec<(usize, A)> = dataset
+            .axis_iter(Axis(0))
+            .into_par_iter()
+            .enumerate()
+            .map(|(i, point)| {
+                let parent = tree
+                    .within(&contiguous(&point), max_distance)?
+                    .into_iter()
+                    .filter(|(_, j)| density_order(&densities, *j, i) == Ordering::Less)
+                    .min_by(compare_neighbors);
+                Ok(parent.map_or((i, A::zero()), |(d, j)| (j, d)))
+            })
+            .collect::<Result<_>>()?;
+        let (parents, link_distances): (Vec<usize>, Vec<A>) = links.into_iter().unzip();
+
+        let (modes, labels) = cut_links(&densities, &parents, &link_distances, max_distance);
+        Ok(QuickShiftResult {
+            labels,
+            modes,
+            densities,
+            parents,
+            link_distances,
+            bandwidth,
+            max_distance,
+        })
+    }
+}
+
+/// Orders points by decreasing density, breaking ties by the smaller index so that links
+/// never form a cycle.
+fn density_order<A: LibData>(densities: &[A], a: usize, b: usize) -> Ordering {
+    densities[b]
+        .partial_cmp(&densities[a])
+        .unwrap_or(Ordering::Equal)
+        .then(a.cmp(&b))
+}
+
+/// Modes, ordered by decreasing density, and labels of the forest without the links longer
+/// than `max_distance`.
+pub(crate) fn cut_links<A: LibData>(
+    densities: &[A],
+    parents: &[usize],
+    link_distances: &[A],
+    max_distance: A,
+) -> (Vec<usize>, Vec<i32>) {
+    let parents: Vec<usize> = parents
+        .iter()
+        .zip(link_distances.iter())
+        .enumerate()
+        .map(|(i, (parent, d))| if *d > max_distance { i } else { *parent })
+        .collect();
+    let roots = forest_roots(&parents);
+
+    let mut modes: Vec<usize> = (0..parents.len()).filter(|i| roots[*i] == *i).collect();
+    modes.sort_by(|a, b| density_order(densities, *a, *b));
+    let mut label_of = vec![0; parents.len()];
+    for (label, mode) in modes.iter().enumerate() {
+        label_of[*mode] = label as i32;
+    }
+    let labels = roots.iter().map(|root| label_of[*root]).collect();
+    (modes, labels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distance_measure::{Euclidean, DTW};
+    use crate::kernel::Gaussian;
+    use crate::test_utils::{circle_blobs, read_data};
+
+    #[test]
+    fn test_quick_shift() {
+        let centers = [[0.0, 0.0], [5.0, 0.0], [0.0, 5.0]];
+        let mut dataset = circle_blobs(&centers, 12, 1.0);
+        dataset
+            .append(Axis(0), circle_blobs(&centers, 1, 0.0).view())
+            .unwrap();
+        let mut quick_shift =
+            QuickShift::new(Euclidean, Some(1.5), Some(2.0)).with_kernel(Gaussian::default());
+        let result = quick_shift.cluster(dataset.view()).unwrap();
+
+        assert_eq!(3, result.n_clusters());
+        let mut modes = result.modes.clone();
+        modes.sort_unstable();
+        assert_eq!(vec![36, 37, 38], modes);
+        for (i, label) in result.labels.iter().take(36).enumerate() {
+            assert_eq!(result.labels[result.modes[*label as usize]], *label);
+            assert_eq!(result.labels[36 + i / 12], *label);
+        }
+        assert_eq!(
+            (result.modes.clone(), result.labels.clone()),
+            result.labels_at(2.0)
+        );
+    }
+
+    #[test]
+    fn test_quick_shift_hierarchy() {
+        let dataset = circle_blobs(&[[0.0, 0.0], [3.0, 0.0], [20.0, 0.0]], 12, 1.0);
+        let mut quick_shift =
+            QuickShift::new(Euclidean, Some(1.0), Some(5.0)).with_kernel(Gaussian::default());
+        let result = quick_shift.cluster(dataset.view()).unwrap();
+
+        assert_eq!(2, result.n_clusters());
+        assert_eq!(3, result.labels_at(1.5).0.len());
+        assert_eq!(dataset.shape()[0], result.labels_at(0.1).0.len());
+    }
+
+    #[test]
+    fn test_quick_shift_dtw_runs_without_errors() {
+        let dataset = read_data::<f64>("data/test.csv");
+        let mut quick_shift = QuickShift::new(DTW, None, None);
+        let result = quick_shift.cluster(dataset.view()).unwrap();
+        assert_eq!(dataset.shape()[0], result.labels.len());
+    }
+}
@@ -0,0 +1,184 @@
+use crate::neighbors::IndexBackend;
+use crate::quick_shift::cut_links;
+use crate::utils::LibData;
+use ndarray::Array2;
+use std::collections::HashMap;
+
+/// Outcome of a `MeanShift` run.
+///
+/// `labels` has one entry per row of the clustered data and indexes into the rows of
+/// `cluster_centers`. The per-seed vectors (`iterations`, `neighbor_counts`, `hit_max_iter`
+/// and `seed_modes`) have one entry per trajectory, in the order of the seeds. `support`
+/// holds, for every mode, the number of points within the bandwidth around it.
+#[derive(Clone, Debug)]
+pub struct ClusteringResult<A: LibData> {
+    pub labels: Vec<i32>,
+    pub cluster_centers: Array2<A>,
+    pub bandwidth: A,
+    /// Bandwidth matrix of an anisotropic bandwidth, which `bandwidth` scales.
+    pub bandwidth_matrix: Option<Array2<A>>,
+    /// Bandwidth of every point in adaptive mode.
+    pub point_bandwidths: Option<Vec<A>>,
+    pub iterations: Vec<usize>,
+    pub neighbor_counts: Vec<usize>,
+    pub hit_max_iter: Vec<bool>,
+    /// Mode each seed converged to, or `None` if the seed was discarded by `min_bin_freq`.
+    pub seed_modes: Vec<Option<usize>>,
+    pub support: Vec<usize>,
+    /// Backend of the neighbor index over the clustered rows.
+    pub index_backend: IndexBackend,
+}
+
+impl<A: LibData> ClusteringResult<A> {
+    pub fn n_clusters(&self) -> usize {
+        self.cluster_centers.shape()[0]
+    }
+
+    pub fn n_unconverged(&self) -> usize {
+        self.hit_max_iter.iter().filter(|x| **x).count()
+    }
+
+    /// How well the labels of this run, e.g. with an approximate `IndexBackend::Hnsw`, agree
+    /// with those of an `exact` run on the same data.
+    pub fn agreement(&self, exact: &ClusteringResult<A>) -> LabelAgreement {
+        LabelAgreement::new(&self.labels, &exact.labels)
+    }
+}
+
+/// Agreement of approximate labels with exact labels of the same points.
+///
+/// `matched_fraction` is the fraction of points that end up in the exact cluster sharing the
+/// most points with their approximate cluster. `adjusted_rand_index` is 1 for the same
+/// partition, whatever the cluster ids, and around 0 for unrelated ones. Noise counts as one
+/// more cluster.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LabelAgreement {
+    pub matched_fraction: f64,
+    pub adjusted_rand_index: f64,
+    pub n_clusters_approximate: usize,
+    pub n_clusters_exact: usize,
+}
+
+impl LabelAgreement {
+    pub fn new(approximate: &[i32], exact: &[i32]) -> Self {
+        debug_assert_eq!(approximate.len(), exact.len());
+        let mut pairs: HashMap<(i32, i32), usize> = HashMap::new();
+        let mut approximate_sizes: HashMap<i32, usize> = HashMap::new();
+        let mut exact_sizes: HashMap<i32, usize> = HashMap::new();
+        for (a, e) in approximate.iter().zip(exact.iter()) {
+            *pairs.entry((*a, *e)).or_insert(0) += 1;
+            *approximate_sizes.entry(*a).or_insert(0) += 1;
+            *exact_sizes.entry(*e).or_insert(0) += 1;
+        }
+
+        let mut best_overlaps: HashMap<i32, usize> = HashMap::new();
+        for ((a, _), count) in pairs.iter() {
+            let best = best_overlaps.entry(*a).or_insert(0);
+            *best = (*best).max(*count);
+        }
+        let n_points = approximate.len().min(exact.len());
+        let matched_fraction = if n_points == 0 {
+            1.0
+        } else {
+            best_overlaps.values().sum::<usize>() as f64 / n_points as f64
+        };
+
+        let pairs_of = |n: usize| (n * n.saturating_sub(1) / 2) as f64;
+        let index: f64 = pairs.values().map(|n| pairs_of(*n)).sum();
+        let approximate_pairs: f64 = approximate_sizes.values().map(|n| pairs_of(*n)).sum();
+        let exact_pairs: f64 = exact_sizes.values().map(|n| pairs_of(*n)).sum();
+        let expected = if n_points < 2 {
+            0.0
+        } else {
+            approximate_pairs * exact_pairs / pairs_of(n_points)
+        };
+        let maximum = (approximate_pairs + exact_pairs) / 2.0;
+        let adjusted_rand_index = if maximum == expected {
+            1.0
+        } else {
+            (index - expected) / (maximum - expected)
+        };
+
+        Self {
+            matched_fraction,
+            adjusted_rand_index,
+            n_clusters_approximate: approximate_sizes.len(),
+            n_clusters_exact: exact_sizes.len(),
+        }
+    }
+}
+
+/// Outcome of a `MedoidShift` run.
+///
+/// `labels` has one entry per point and indexes into `medoids`, the points the clusters end
+/// up at. `support` holds the number of points in every cluster, and `iterations` the number of
+/// medoid shift passes.
+#[derive(Clone, Debug)]
+pub struct MedoidShiftResult<A: LibData> {
+    pub labels: Vec<i32>,
+    pub medoids: Vec<usize>,
+    pub bandwidth: A,
+    pub support: Vec<usize>,
+    pub iterations: usize,
+}
+
+impl<A: LibData> MedoidShiftResult<A> {
+    pub fn n_clusters(&self) -> usize {
+        self.medoids.len()
+    }
+}
+
+/// Outcome of a `QuickShift` run.
+///
+/// Every point links to its nearest neighbor of higher density within `max_distance`, and the
+/// trees of these links are the clusters. `labels` indexes into `modes`, the roots of the
+/// trees, ordered by decreasing density. `parents` holds the neighbor every point links to,
+/// roots being their own parents, and `link_distances` the length of every link.
+#[derive(Clone, Debug)]
+pub struct QuickShiftResult<A: LibData> {
+    pub labels: Vec<i32>,
+    pub modes: Vec<usize>,
+    pub densities: Vec<A>,
+    pub parents: Vec<usize>,
+    pub link_distances: Vec<A>,
+    pub bandwidth: A,
+    pub max_distance: A,
+}
+
+impl<A: LibData> QuickShiftResult<A> {
+    pub fn n_clusters(&self) -> usize {
+        self.modes.len()
+    }
+
+    /// Modes and labels after cutting all links longer than `max_distance`. Thresholds above
+    /// the `max_distance` of the run give the same clusters as the run.
+    pub fn labels_at(&self, max_distance: A) -> (Vec<usize>, Vec<i32>) {
+        cut_links(
+            &self.densities,
+            &self.parents,
+            &self.link_distances,
+            max_distance,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::close_l1;
+
+    #[test]
+    fn test_label_agreement() {
+        let exact = [0, 0, 0, 1, 1, 1, -1];
+        let renamed = LabelAgreement::new(&[1, 1, 1, 0, 0, 0, -1], &exact);
+        assert_eq!(1.0, renamed.matched_fraction);
+        close_l1(1.0, renamed.adjusted_rand_index, 1e-12);
+        assert_eq!(3, renamed.n_clusters_approximate);
+
+        let merged = LabelAgreement::new(&[0, 0, 0, 0, 0, 0, -1], &exact);
+        close_l1(4.0 / 7.0, merged.matched_fraction, 1e-12);
+        assert!(merged.adjusted_rand_index < 0.5);
+        assert_eq!(2, merged.n_clusters_approximate);
+        assert_eq!(3, merged.n_clusters_exact);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::distance_measure::DistanceMeasure;
+use crate::error::{MeanShiftError, Result};
+use crate::utils::{contiguous, LibData};
+use ndarray::{Array1, ArrayView1, ArrayView2, Axis, CowArray, Ix2};
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+use rand::SeedableRng;
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+/// Strategy that decides from which points the mean shift trajectories start.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum Seeding {
+    /// Start a trajectory from every row of the dataset.
+    #[default]
+    All,
+    /// Start from the centers of grid bins of the size of the bandwidth that hold at least
+    /// `min_bin_freq` rows (like sklearn's `bin_seeding`), counting every row with its weight.
+    Bin,
+    /// Start from `n_seeds` distinct rows drawn with an RNG seeded by `seed`.
+    Random { n_seeds: usize, seed: u64 },
+    /// Start from `n_seeds` rows picked by farthest-point sampling, beginning with the first row.
+    FarthestPoint { n_seeds: usize },
+}
+
+impl Seeding {
+    pub(crate) fn seeds<'a, A: LibData, D: DistanceMeasure<A>>(
+        &self,
+        data: ArrayView2<'a, A>,
+        weights: Option<ArrayView1<A>>,
+        bandwidth: A,
+        min_bin_freq: usize,
+    ) -> Result<CowArray<'a, A, Ix2>> {
+        match self {
+            Self::All => Ok(CowArray::from(data)),
+            Self::Bin => bin_seeds(data, weights, bandwidth, min_bin_freq),
+            Self::Random { n_seeds, seed } => {
+                let indices = sample(
+                    &mut StdRng::seed_from_u64(*seed),
+                    data.shape()[0],
+                    (*n_seeds).min(data.shape()[0]),
+                )
+                .into_vec();
+                Ok(CowArray::from(data.select(Axis(0), &indices)))
+            }
+            Self::FarthestPoint { n_seeds } => {
+                let indices = farthest_point_indices::<A, D>(data, *n_seeds);
+                Ok(CowArray::from(data.select(Axis(0), &indices)))
+            }
+        }
+    }
+}
+
+fn bin_seeds<'a, A: LibData>(
+    data: ArrayView2<'a, A>,
+    weights: Option<ArrayView1<A>>,
+    bin_size: A,
+    min_bin_freq: usize,
+) -> Result<CowArray<'a, A, Ix2>> {
+    let mut bins: HashMap<Vec<i64>, A> = HashMap::new();
+    for (i, point) in data.axis_iter(Axis(0)).enumerate() {
+        let bin: Vec<i64> = point
+            .iter()
+            .map(|x| (*x / bin_size).round().to_i64().unwrap_or(i64::MAX))
+            .collect();
+        let weight = weights.map_or(A::one(), |w| w[i]);
+        let freq = bins.entry(bin).or_insert_with(A::zero);
+        *freq = *freq + weight;
+    }
+
+    let min_weight = A::from_usize(min_bin_freq).unwrap();
+    let mut bins: Vec<Vec<i64>> = bins
+        .into_iter()
+        .filter(|(_, freq)| *freq >= min_weight)
+        .map(|(bin, _)| bin)
+        .collect();
+
+    if bins.is_empty() {
+        return Err(MeanShiftError::NoModeFound { min_bin_freq });
+    }
+    if bins.len() == data.shape()[0] {
+        // every point has a bin of its own, so binning saves nothing
+        return Ok(CowArray::from(data));
+    }
+
+    bins.sort();
+    let seeds: Array1<A> = bins
+        .into_iter()
+        .flatten()
+        .map(|x| A::from_i64(x).unwrap() * bin_size)
+        .collect();
+    let n_seeds = seeds.len() / data.shape()[1];
+    Ok(CowArray::from(
+        seeds.into_shape((n_seeds, data.shape()[1]))?,
+    ))
+}
+
+fn farthest_point_indices<A: LibData, D: DistanceMeasure<A>>(
+    data: ArrayView2<A>,
+    n_seeds: usize,
+) -> Vec<usize> {
+    let n_seeds = n_seeds.min(data.shape()[0]);
+    if n_seeds == 0 {
+        return vec![];
+    }
+
+    let mut indices = vec![0];
+    let mut min_distances: Vec<A> = vec![A::INFINITY; data.shape()[0]];
+    while indices.len() < n_seeds {
+        let last = data.index_axis(Axis(0), *indices.last().unwrap());
+        let last = contiguous(&last);
+        min_distances
+            .par_iter_mut()
+            .zip(data.axis_iter(Axis(0)).into_par_iter())
+            .for_each(|(min_distance, point)| {
+                let distance = D::distance_slice(&contiguous(&point), &last);
+                *min_distance = min_distance.min(distance);
+            });
+        let (next, _) =
+            min_distances
+                .iter()
+                .enumerate()
+                .fold((0, A::min_value()), |(max_i, max), (i, x)| {
+                    if *x > max {
+                        (i, *x)
+                    } else {
+                        (max_i, max)
+                    }
+                });
+        indices.push(next);
+    }
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distance_measure::Euclidean;
+    use ndarray::{arr2, Array2};
+
+    fn dataset() -> Array2<f64> {
+        arr2(&[
+            [0.0, 0.0],
+            [0.1, 0.1],
+            [0.2, 0.0],
+            [5.0, 5.0],
+            [5.1, 5.0],
+            [9.0, 0.0],
+        ])
+    }
+
+    #[test]
+    fn test_bin_seeding() {
+        let data = dataset();
+        let seeds = Seeding::Bin
+            .seeds::<_, Euclidean>(data.view(), None, 1.0, 2)
+            .unwrap();
+
+        assert_eq!(seeds, arr2(&[[0.0, 0.0], [5.0, 5.0]]));
+    }
+
+    #[test]
+    fn test_random_seeding_is_reproducible() {
+        let data = dataset();
+        let seeding = Seeding::Random {
+            n_seeds: 3,
+            seed: 42,
+        };
+        let a = seeding
+            .seeds::<_, Euclidean>(data.view(), None, 1.0, 1)
+            .unwrap();
+        let b = seeding
+            .seeds::<_, Euclidean>(data.view(), None, 1.0, 1)
+            .unwrap();
+
+        assert_eq!(a.shape(), &[3, 2]);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_farthest_point_seeding() {
+        let data = dataset();
+        let seeds = Seeding::FarthestPoint { n_seeds: 3 }
+            .seeds::<_, Euclidean>(data.view(), None, 1.0, 1)
+            .unwrap();
+
+        assert_eq!(seeds, arr2(&[[0.0, 0.0], [9.0, 0.0], [5.0, 5.0]]));
+    }
+}
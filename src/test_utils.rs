@@ -1,6 +1,7 @@
 use crate::utils::LibData;
 use csv::{ReaderBuilder, Trim};
 use ndarray::{Array1, Array2};
+use std::f64::consts::PI;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
@@ -24,7 +25,6 @@ pub(crate) fn read_data<A: LibData>(file_path: &str) -> Array2<A> {
 
     let flat_data: Array1<A> = reader
         .records()
-        .into_iter()
         .flat_map(|rec| {
             rec.unwrap()
                 .iter()
@@ -37,3 +37,16 @@ pub(crate) fn read_data<A: LibData>(file_path: &str) -> Array2<A> {
         .into_shape((n_rows, n_columns))
         .expect("Could not deserialize sent data")
 }
+
+/// `n` points evenly spread on a circle of `radius` around each of the `centers`.
+pub(crate) fn circle_blobs(centers: &[[f64; 2]], n: usize, radius: f64) -> Array2<f64> {
+    let mut data = Array2::zeros([centers.len() * n, 2]);
+    for (c, center) in centers.iter().enumerate() {
+        for i in 0..n {
+            let angle = 2.0 * PI * i as f64 / n as f64;
+            data[[c * n + i, 0]] = center[0] + radius * angle.cos();
+            data[[c * n + i, 1]] = center[1] + radius * angle.sin();
+        }
+    }
+    data
+}
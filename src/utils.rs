@@ -1,12 +1,17 @@
-use anyhow::Result;
+use crate::error::{MeanShiftError, Result};
+use crate::params::ParameterError;
+use crate::DistanceMeasure;
 use ndarray::{
-    concatenate, s, ArcArray1, Array1, Array2, Array3, ArrayView2, ArrayView3, Axis, ScalarOperand,
+    concatenate, s, ArcArray1, Array1, Array2, Array3, ArrayBase, ArrayView1, ArrayView2,
+    ArrayView3, Axis, Data, Ix1, ScalarOperand,
 };
 use num_traits::{Float, FromPrimitive};
+use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::fmt::{Debug, Display};
 use std::iter::Sum;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 
 pub trait LibData:
     'static
@@ -51,13 +56,23 @@ pub trait SliceComp {
     fn slice_cmp(&self, b: &Self) -> Ordering;
 }
 
+/// Borrows the elements of `row` as a slice, copying them only if `row` is not contiguous.
+pub fn contiguous<A: LibData, S: Data<Elem = A>>(row: &ArrayBase<S, Ix1>) -> Cow<'_, [A]> {
+    match row.as_slice() {
+        Some(slice) => Cow::Borrowed(slice),
+        None => Cow::Owned(row.to_vec()),
+    }
+}
+
 impl<A: LibData> SliceComp for Array1<A> {
     fn slice_cmp(&self, other: &Self) -> Ordering {
         debug_assert!(self.len() == other.len());
-        let a = self.as_slice().unwrap();
-        let b = other.as_slice().unwrap();
+        let a = contiguous(self);
+        let b = contiguous(other);
         for i in 0..b.len() {
-            let cmp = a[i].partial_cmp(&b[i]).unwrap();
+            let cmp = a[i]
+                .partial_cmp(&b[i])
+                .unwrap_or_else(|| a[i].is_nan().cmp(&b[i].is_nan()));
             if cmp.ne(&Ordering::Equal) {
                 return cmp;
             }
@@ -84,9 +99,13 @@ pub fn nanmean<A: LibData>(arr: ArrayView3<A>, axis: Axis) -> Result<Array2<A>>
 }
 
 /// Can only work with univariate time series for now.
-pub fn time_series_to_matrix<A: LibData>(series: &Vec<ArrayView2<A>>) -> Array3<A> {
+pub fn time_series_to_matrix<A: LibData>(series: &Vec<ArrayView2<A>>) -> Result<Array3<A>> {
     let n_rows = series.len();
-    let max_cols = series.iter().map(|s| s.len()).max().unwrap();
+    let max_cols = series
+        .iter()
+        .map(|s| s.len())
+        .max()
+        .ok_or(MeanShiftError::EmptyInput)?;
     let variates = series[0].shape()[1];
     let mut matrix: Array3<A> = Array3::zeros([n_rows, max_cols, variates]) + A::NAN;
 
@@ -96,22 +115,139 @@ pub fn time_series_to_matrix<A: LibData>(series: &Vec<ArrayView2<A>>) -> Array3<
         row.assign(s)
     }
 
-    matrix
+    Ok(matrix)
 }
 
 pub fn to_time_series_real_size<A: LibData>(series: ArrayView2<A>) -> Result<Array2<A>> {
     let array_views: Vec<ArrayView2<A>> = series
         .axis_iter(Axis(0))
-        .filter_map(|p| {
-            p.iter()
-                .all(|x| !x.is_nan())
-                .then(|| Some(p.insert_axis(Axis(0))))
-        })
-        .map(|x| x.unwrap())
+        .filter(|p| p.iter().all(|x| !x.is_nan()))
+        .map(|p| p.insert_axis(Axis(0)))
         .collect();
     Ok(concatenate(Axis(0), &array_views)?)
 }
 
+/// Checks that `data` is non-empty and finite. If the distance measure
+/// allows NaN padding, rows may end with NaNs, but not consist of them only.
+pub(crate) fn validate_data<A: LibData, D: DistanceMeasure<A>>(data: ArrayView2<A>) -> Result<()> {
+    if data.shape()[0] == 0 || data.shape()[1] == 0 {
+        return Err(MeanShiftError::EmptyInput);
+    }
+    for (row, values) in data.axis_iter(Axis(0)).enumerate() {
+        let mut padding_start = None;
+        for (column, value) in values.iter().enumerate() {
+            if D::ALLOWS_NAN_PADDING && value.is_nan() {
+                padding_start.get_or_insert(column);
+            } else if !value.is_finite() || padding_start.is_some() {
+                return Err(MeanShiftError::NonFiniteValue {
+                    row,
+                    column: padding_start.unwrap_or(column),
+                });
+            }
+        }
+        if padding_start == Some(0) {
+            return Err(MeanShiftError::NonFiniteValue { row, column: 0 });
+        }
+    }
+    Ok(())
+}
+
+/// Checks that there is one finite, non-negative weight per row and that they sum to a
+/// positive value.
+pub(crate) fn validate_weights<A: LibData>(weights: ArrayView1<A>, n_rows: usize) -> Result<()> {
+    if weights.len() != n_rows {
+        return Err(MeanShiftError::WeightCountMismatch {
+            expected: n_rows,
+            actual: weights.len(),
+        });
+    }
+    if let Some(weight) = weights.iter().find(|w| !w.is_finite() || **w < A::zero()) {
+        return Err(ParameterError::InvalidWeight(weight.to_f64().unwrap_or(f64::NAN)).into());
+    }
+    if weights.sum() <= A::zero() {
+        return Err(ParameterError::NonPositiveWeightSum.into());
+    }
+    Ok(())
+}
+
+/// Root of the tree of every node in the forest given by the `parents` of the nodes. Roots are
+/// their own parents; a cycle is rooted at its smallest node.
+pub(crate) fn forest_roots(parents: &[usize]) -> Vec<usize> {
+    let mut roots: Vec<Option<usize>> = vec![None; parents.len()];
+    for start in 0..parents.len() {
+        let mut path = vec![];
+        let mut node = start;
+        let root = loop {
+            if let Some(root) = roots[node] {
+                break root;
+            }
+            if let Some(position) = path.iter().position(|n| *n == node) {
+                break *path[position..].iter().min().unwrap();
+            }
+            path.push(node);
+            if parents[node] == node {
+                break node;
+            }
+            node = parents[node];
+        };
+        for node in path {
+            roots[node] = Some(root);
+        }
+    }
+    roots.into_iter().map(|root| root.unwrap()).collect()
+}
+
+/// Union-find over `0..n` that can merge sets from several threads at once. Every set is
+/// rooted at its smallest element, so the sets and their roots do not depend on the order of
+/// the merges.
+pub(crate) struct UnionFind {
+    parents: Vec<AtomicUsize>,
+}
+
+impl UnionFind {
+    pub fn new(n: usize) -> Self {
+        Self {
+            parents: (0..n).map(AtomicUsize::new).collect(),
+        }
+    }
+
+    pub fn find(&self, mut x: usize) -> usize {
+        loop {
+            let parent = self.parents[x].load(AtomicOrdering::Acquire);
+            if parent == x {
+                return x;
+            }
+            let grandparent = self.parents[parent].load(AtomicOrdering::Acquire);
+            // path halving, a lost race only skips the shortcut
+            let _ = self.parents[x].compare_exchange(
+                parent,
+                grandparent,
+                AtomicOrdering::AcqRel,
+                AtomicOrdering::Acquire,
+            );
+            x = grandparent;
+        }
+    }
+
+    pub fn union(&self, a: usize, b: usize) {
+        let (mut a, mut b) = (a, b);
+        loop {
+            a = self.find(a);
+            b = self.find(b);
+            if a == b {
+                return;
+            }
+            let (root, child) = if a < b { (a, b) } else { (b, a) };
+            if self.parents[child]
+                .compare_exchange(child, root, AtomicOrdering::AcqRel, AtomicOrdering::Acquire)
+                .is_ok()
+            {
+                return;
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -121,20 +257,25 @@ mod tests {
     fn test_time_series_to_matrix() {
         let timeseries: Vec<Array2<f64>> = vec![arr2(&[[0.0, 1.0, 2.0]]), arr2(&[[3.0, 4.0]])];
 
-        let matrix = time_series_to_matrix(&timeseries.iter().map(|x| x.t()).collect());
+        let matrix = time_series_to_matrix(&timeseries.iter().map(|x| x.t()).collect()).unwrap();
 
         println!("{:?}", matrix);
 
         assert_eq!(timeseries[0][[0, 0]], matrix[[0, 0, 0]]);
         assert_eq!(timeseries[0][[0, 1]], matrix[[0, 1, 0]]);
         assert!(matrix[[1, 2, 0]].is_nan());
+
+        assert_eq!(
+            time_series_to_matrix::<f64>(&vec![]),
+            Err(MeanShiftError::EmptyInput)
+        );
     }
 
     #[test]
     fn test_to_time_series_real_size() {
         let timeseries: Vec<Array2<f64>> = vec![arr2(&[[0.0, 1.0, 2.0]]), arr2(&[[3.0, 4.0]])];
 
-        let matrix = time_series_to_matrix(&timeseries.iter().map(|x| x.t()).collect());
+        let matrix = time_series_to_matrix(&timeseries.iter().map(|x| x.t()).collect()).unwrap();
 
         assert_eq!(
             to_time_series_real_size(matrix.index_axis(Axis(0), 0))
@@ -162,4 +303,44 @@ mod tests {
         let avg = nanmean(dataset.view(), Axis(0)).unwrap();
         assert_eq!(avg, expected)
     }
+
+    #[test]
+    fn test_validate_data() {
+        use crate::distance_measure::{Euclidean, DTW};
+
+        let data = arr2(&[[0.0, 1.0, 2.0], [3.0, f64::NAN, f64::NAN]]);
+
+        assert_eq!(
+            validate_data::<_, Euclidean>(data.view()),
+            Err(MeanShiftError::NonFiniteValue { row: 1, column: 1 })
+        );
+        assert_eq!(validate_data::<_, DTW>(data.view()), Ok(()));
+        assert_eq!(
+            validate_data::<_, DTW>(arr2(&[[f64::NAN, 1.0]]).view()),
+            Err(MeanShiftError::NonFiniteValue { row: 0, column: 0 })
+        );
+        assert_eq!(
+            validate_data::<f64, Euclidean>(Array2::zeros([0, 3]).view()),
+            Err(MeanShiftError::EmptyInput)
+        );
+    }
+
+    #[test]
+    fn test_forest_roots() {
+        assert_eq!(forest_roots(&[0, 0, 1, 3, 2]), vec![0, 0, 0, 3, 0]);
+        assert_eq!(forest_roots(&[1, 2, 1, 0]), vec![1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_union_find() {
+        use rayon::prelude::*;
+
+        let union_find = UnionFind::new(100);
+        (0..99usize)
+            .into_par_iter()
+            .filter(|i| i % 10 != 9)
+            .for_each(|i| union_find.union(i + 1, i));
+        let roots: Vec<usize> = (0..100).map(|i| union_find.find(i)).collect();
+        assert_eq!(roots, (0..100).map(|i| i / 10 * 10).collect::<Vec<_>>());
+    }
 }